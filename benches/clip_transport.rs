@@ -0,0 +1,147 @@
+// compares the two ways of getting samples from the audio callback to the analyzer:
+// appending to a buffer behind a mutex, like before, and push_frames into a lock-free queue,
+// like SoundProxy does now. both de-interleave the same callback buffer, both have a reader
+// draining the other end as fast as it can, both get callbacks at the same pace,
+// what's measured is the whole audio callback. a callback that waits on the reader is what
+// makes sound cards drop out, so besides criterion's mean the slowest callbacks get printed too
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use rtrb::RingBuffer;
+
+use rustcertation::sample_queue::{push_frames, stereo_frames};
+
+// stereo interleaved samples either transport holds before it drops new ones
+const QUEUE_CAP: usize = 8 * 2 * 4096;
+// a typical callback from a 4 channel interface, analyzing channels 1 and 2
+const CALLBACK_FRAMES: usize = 512;
+const NUM_CHANNELS: usize = 4;
+// much more often than a sound card calls back, but rare enough for the reader to keep up,
+// so the callbacks actually write instead of finding the buffer full
+const CALLBACK_GAP: Duration = Duration::from_micros(20);
+
+// runs `work` over and over until the returned flag is set
+fn spawn_reader<T: Send + 'static>(
+    mut state: T,
+    mut work: impl FnMut(&mut T) + Send + 'static,
+) -> (Arc<AtomicBool>, JoinHandle<()>) {
+    let done = Arc::new(AtomicBool::new(false));
+    let done_clone = done.clone();
+
+    let thread = thread::spawn(move || {
+        while !done_clone.load(Ordering::Relaxed) {
+            work(&mut state);
+        }
+    });
+
+    (done, thread)
+}
+
+// times `callback` alone, waiting CALLBACK_GAP between calls, and keeps every call's time
+fn time_paced(iters: u64, times: &mut Vec<Duration>, mut callback: impl FnMut()) -> Duration {
+    (0..iters)
+        .map(|_| {
+            let due = Instant::now() + CALLBACK_GAP;
+            while Instant::now() < due {
+                std::hint::spin_loop();
+            }
+
+            let start = Instant::now();
+            callback();
+            let time = start.elapsed();
+            times.push(time);
+            time
+        })
+        .sum()
+}
+
+// the worst case is what decides whether the sound card drops out
+fn print_tail(name: &str, times: &mut [Duration]) {
+    times.sort_unstable();
+    let percentile = |fraction: f64| times[((times.len() - 1) as f64 * fraction) as usize];
+
+    println!(
+        "{}: p99 {:?}, p99.9 {:?}, max {:?} over {} callbacks",
+        name,
+        percentile(0.99),
+        percentile(0.999),
+        times[times.len() - 1],
+        times.len()
+    );
+}
+
+fn callback_data() -> Vec<i16> {
+    (0..CALLBACK_FRAMES * NUM_CHANNELS)
+        .map(|index| (index * 31 % 2048) as i16)
+        .collect()
+}
+
+fn mutex_buffer(c: &mut Criterion) {
+    let data = callback_data();
+    let buffer = Arc::new(Mutex::new(Vec::<f32>::with_capacity(QUEUE_CAP)));
+
+    // the analyzer side, taking everything there is
+    let (done, reader) = spawn_reader(
+        (buffer.clone(), Vec::with_capacity(QUEUE_CAP)),
+        |(buffer, taken)| {
+            taken.clear();
+            taken.extend(buffer.lock().expect("locked buffer in reader").drain(..));
+            criterion::black_box(&taken);
+        },
+    );
+
+    let mut times = Vec::new();
+    c.bench_function("mutex buffer callback", |b| {
+        b.iter_custom(|iters| {
+            time_paced(iters, &mut times, || {
+                let mut buffer = buffer.lock().expect("locked buffer in callback");
+                // same dropping policy as the queue
+                let room = (QUEUE_CAP - buffer.len()) & !1;
+                buffer.extend(stereo_frames(&data, NUM_CHANNELS, 0, 1).take(room));
+            })
+        })
+    });
+
+    done.store(true, Ordering::Relaxed);
+    reader.join().expect("joined reader");
+    print_tail("mutex buffer callback", &mut times);
+}
+
+fn queue(c: &mut Criterion) {
+    let data = callback_data();
+    let (mut producer, consumer) = RingBuffer::new(QUEUE_CAP);
+
+    // the analyzer side, taking everything there is
+    let (done, reader) = spawn_reader(
+        (consumer, Vec::with_capacity(QUEUE_CAP)),
+        |(consumer, taken)| {
+            taken.clear();
+            if let Ok(chunk) = consumer.read_chunk(consumer.slots()) {
+                let (first, second) = chunk.as_slices();
+                taken.extend_from_slice(first);
+                taken.extend_from_slice(second);
+                chunk.commit_all();
+            }
+            criterion::black_box(&taken);
+        },
+    );
+
+    let mut times = Vec::new();
+    c.bench_function("queue callback", |b| {
+        b.iter_custom(|iters| {
+            time_paced(iters, &mut times, || {
+                push_frames(&mut producer, &data, NUM_CHANNELS, 0, 1)
+            })
+        })
+    });
+
+    done.store(true, Ordering::Relaxed);
+    reader.join().expect("joined reader");
+    print_tail("queue callback", &mut times);
+}
+
+criterion_group!(benches, mutex_buffer, queue);
+criterion_main!(benches);
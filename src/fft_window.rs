@@ -0,0 +1,201 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+use spectrum_analyzer::windows;
+
+// sidelobes about as low as Blackman-Harris
+const KAISER_BETA: f32 = 8.6f32;
+
+// what the samples get shaped with before the FFT,
+// narrower main lobes resolve close tones, lower sidelobes leak less
+#[derive(Debug, Clone, Copy)]
+pub enum WindowFunction {
+    Hann,
+    Hamming,
+    BlackmanHarris,
+    FlatTop,
+    Rectangular,
+    Kaiser,
+}
+
+impl WindowFunction {
+    pub fn next(self) -> Self {
+        match self {
+            WindowFunction::Hann => WindowFunction::Hamming,
+            WindowFunction::Hamming => WindowFunction::BlackmanHarris,
+            WindowFunction::BlackmanHarris => WindowFunction::FlatTop,
+            WindowFunction::FlatTop => WindowFunction::Rectangular,
+            WindowFunction::Rectangular => WindowFunction::Kaiser,
+            WindowFunction::Kaiser => WindowFunction::Hann,
+        }
+    }
+
+    // the weight of every sample, worth computing once and multiplying in for every frame
+    pub fn coefficients(self, len: usize) -> Vec<f32> {
+        let ones = vec![1f32; len];
+
+        match self {
+            WindowFunction::Hann => windows::hann_window(&ones),
+            WindowFunction::Hamming => windows::hamming_window(&ones),
+            WindowFunction::BlackmanHarris => windows::blackman_harris_4term(&ones),
+            WindowFunction::FlatTop => flat_top_window(&ones),
+            WindowFunction::Rectangular => ones,
+            WindowFunction::Kaiser => kaiser_window(&ones, KAISER_BETA),
+        }
+    }
+}
+
+impl fmt::Display for WindowFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            WindowFunction::Hann => "hann",
+            WindowFunction::Hamming => "hamming",
+            WindowFunction::BlackmanHarris => "blackman-harris",
+            WindowFunction::FlatTop => "flat-top",
+            WindowFunction::Rectangular => "rectangular",
+            WindowFunction::Kaiser => "kaiser",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for WindowFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hann" => Ok(WindowFunction::Hann),
+            "hamming" => Ok(WindowFunction::Hamming),
+            "blackman-harris" => Ok(WindowFunction::BlackmanHarris),
+            "flat-top" => Ok(WindowFunction::FlatTop),
+            "rectangular" => Ok(WindowFunction::Rectangular),
+            "kaiser" => Ok(WindowFunction::Kaiser),
+            _ => Err(format!("unknown window function: {}", s)),
+        }
+    }
+}
+
+// position of each sample from 0 to 1 across the window
+fn positions(len: usize) -> impl Iterator<Item = f32> {
+    let last = (len.max(2) - 1) as f32;
+    (0..len).map(move |n| n as f32 / last)
+}
+
+// wide main lobe, but amplitudes come out right even between bins
+fn flat_top_window(samples: &[f32]) -> Vec<f32> {
+    const A: [f32; 5] = [
+        0.215_578_95,
+        0.416_631_58,
+        0.277_263_16,
+        0.083_578_95,
+        0.006_947_368,
+    ];
+
+    samples
+        .iter()
+        .zip(positions(samples.len()))
+        .map(|(sample, x)| {
+            let weight = A[0] - A[1] * (2f32 * PI * x).cos() + A[2] * (4f32 * PI * x).cos()
+                - A[3] * (6f32 * PI * x).cos()
+                + A[4] * (8f32 * PI * x).cos();
+            sample * weight
+        })
+        .collect()
+}
+
+fn kaiser_window(samples: &[f32], beta: f32) -> Vec<f32> {
+    let denominator = bessel_i0(beta);
+
+    samples
+        .iter()
+        .zip(positions(samples.len()))
+        .map(|(sample, x)| {
+            let r = 2f32 * x - 1f32;
+            sample * bessel_i0(beta * (1f32 - r * r).max(0f32).sqrt()) / denominator
+        })
+        .collect()
+}
+
+// modified Bessel function of the first kind, order 0, by its power series
+fn bessel_i0(x: f32) -> f32 {
+    let half_x = x / 2f32;
+    let mut sum = 1f32;
+    let mut term = 1f32;
+
+    for k in 1..50 {
+        term *= (half_x / k as f32).powi(2);
+        sum += term;
+        if term < sum * 1e-8f32 {
+            break;
+        }
+    }
+
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [WindowFunction; 6] = [
+        WindowFunction::Hann,
+        WindowFunction::Hamming,
+        WindowFunction::BlackmanHarris,
+        WindowFunction::FlatTop,
+        WindowFunction::Rectangular,
+        WindowFunction::Kaiser,
+    ];
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} isn't within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn assert_symmetric(weights: &[f32]) {
+        for (front, back) in weights.iter().zip(weights.iter().rev()) {
+            assert_close(*front, *back, 1e-6);
+        }
+    }
+
+    #[test]
+    fn bessel_i0_matches_known_values() {
+        assert_eq!(bessel_i0(0f32), 1f32);
+        assert_close(bessel_i0(1f32), 1.266_066, 1e-6);
+        assert_close(bessel_i0(3f32), 4.880_793, 1e-5);
+        assert_close(bessel_i0(KAISER_BETA), 750.461_2, 750.461_2 * 1e-5);
+    }
+
+    #[test]
+    fn kaiser_is_symmetric_and_peaks_at_1() {
+        let weights = kaiser_window(&[1f32; 257], KAISER_BETA);
+
+        assert_symmetric(&weights);
+        assert_close(weights[128], 1f32, 1e-6);
+        assert_close(weights[0], 1f32 / bessel_i0(KAISER_BETA), 1e-6);
+        assert!(weights.iter().all(|weight| *weight <= weights[128]));
+    }
+
+    #[test]
+    fn flat_top_is_symmetric_and_1_in_the_middle() {
+        let weights = flat_top_window(&[1f32; 257]);
+
+        assert_symmetric(&weights);
+        // the coefficients sum to 1, which is where every cosine lines up
+        assert_close(weights[128], 1f32, 1e-6);
+    }
+
+    #[test]
+    fn every_window_has_a_weight_per_sample() {
+        for window in ALL {
+            for len in [1, 2, 256, 4096] {
+                assert_eq!(window.coefficients(len).len(), len, "{} of {}", window, len);
+            }
+        }
+    }
+}
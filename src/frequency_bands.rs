@@ -0,0 +1,531 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+// the audible range, what the displays spread over their full height
+pub const MIN_FREQUENCY: f32 = 20f32;
+pub const MAX_FREQUENCY: f32 = 20000f32;
+
+// already more bands than the window has pixels to show them on
+pub const MAX_BANDS: usize = 4096;
+
+// base ten octave ratio and reference frequency from IEC 61260-1
+const OCTAVE_RATIO: f32 = 1.995_262_3f32; // 10^(3/10)
+const REFERENCE_FREQUENCY: f32 = 1000f32;
+
+// ISO 266 preferred numbers, which nominal octave and third octave centers get rounded to
+const PREFERRED_NUMBERS: [f32; 10] = [1f32, 1.25, 1.6, 2f32, 2.5, 3.15, 4f32, 5f32, 6.3, 8f32];
+
+// Zwicker's critical band edges, his 24 bands stop at 15.5 kHz so the scale doesn't reach 20 kHz
+const BARK_EDGES: [f32; 25] = [
+    20f32, 100f32, 200f32, 300f32, 400f32, 510f32, 630f32, 770f32, 920f32, 1080f32, 1270f32,
+    1480f32, 1720f32, 2000f32, 2320f32, 2700f32, 3150f32, 3700f32, 4400f32, 5300f32, 6400f32,
+    7700f32, 9500f32, 12000f32, 15500f32,
+];
+
+// how the spectrum gets split into bands
+#[derive(Debug, Clone, Copy)]
+pub enum BandScale {
+    // as many bands as asked for, equally wide on a log scale
+    Log,
+    // standard fractional octave bands, the number is the fraction: 1, 3, 6 or 12 per octave
+    Octave(u8),
+    // as many overlapping triangular filters as asked for, equally spaced in mels
+    Mel,
+    // the 24 critical bands of hearing
+    Bark,
+}
+
+impl BandScale {
+    pub fn next(self) -> Self {
+        match self {
+            BandScale::Log => BandScale::Octave(1),
+            BandScale::Octave(1) => BandScale::Octave(3),
+            BandScale::Octave(3) => BandScale::Octave(6),
+            BandScale::Octave(6) => BandScale::Octave(12),
+            BandScale::Octave(_) => BandScale::Mel,
+            BandScale::Mel => BandScale::Bark,
+            BandScale::Bark => BandScale::Log,
+        }
+    }
+
+    // num_bands only matters for scales that don't have a fixed set of bands
+    pub fn bands(self, num_bands: usize) -> Vec<Band> {
+        match self {
+            BandScale::Log => log_bands(num_bands),
+            BandScale::Octave(fraction) => octave_bands(fraction),
+            BandScale::Mel => mel_bands(num_bands),
+            BandScale::Bark => bark_bands(),
+        }
+    }
+
+    // what each band ends up as, from the FFT magnitudes
+    pub fn aggregate(self, magnitudes: &[f32], bin_width: f32, bands: &[Band]) -> Vec<f32> {
+        match self {
+            BandScale::Log => aggregate_peaks(magnitudes, bin_width, bands),
+            BandScale::Octave(_) | BandScale::Bark => {
+                aggregate_energy(magnitudes, bin_width, bands)
+            }
+            BandScale::Mel => aggregate_triangles(magnitudes, bin_width, bands),
+        }
+    }
+
+    // whether bands add up the energy of their bins rather than take the loudest one,
+    // which is what decides how a full scale sine reads
+    pub fn sums_energy(self) -> bool {
+        !matches!(self, BandScale::Log)
+    }
+
+    // the name a band goes by on screen
+    pub fn label(self, band: &Band) -> String {
+        match self {
+            BandScale::Octave(1) | BandScale::Octave(3) => format_frequency(nominal(band.center)),
+            _ => format_frequency(round_to_digits(band.center, 3)),
+        }
+    }
+}
+
+impl fmt::Display for BandScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BandScale::Log => write!(f, "log"),
+            BandScale::Octave(1) => write!(f, "octave"),
+            BandScale::Octave(fraction) => write!(f, "1/{} octave", fraction),
+            BandScale::Mel => write!(f, "mel"),
+            BandScale::Bark => write!(f, "bark"),
+        }
+    }
+}
+
+impl FromStr for BandScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "log" => Ok(BandScale::Log),
+            "octave" | "1/1" => Ok(BandScale::Octave(1)),
+            "third-octave" | "1/3" => Ok(BandScale::Octave(3)),
+            "sixth-octave" | "1/6" => Ok(BandScale::Octave(6)),
+            "twelfth-octave" | "1/12" => Ok(BandScale::Octave(12)),
+            "mel" => Ok(BandScale::Mel),
+            "bark" => Ok(BandScale::Bark),
+            _ => Err(format!("unknown band scale: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Band {
+    pub low: f32,
+    pub center: f32,
+    pub high: f32,
+}
+
+// equally wide on a log scale, so every octave gets the same number of bands
+fn log_bands(count: usize) -> Vec<Band> {
+    let ratio = (MAX_FREQUENCY / MIN_FREQUENCY).powf(1f32 / count as f32);
+    let edge = |index: usize| MIN_FREQUENCY * ratio.powi(index as i32);
+
+    (0..count)
+        .map(|index| {
+            let (low, high) = (edge(index), edge(index + 1));
+            Band {
+                low,
+                center: (low * high).sqrt(),
+                high,
+            }
+        })
+        .collect()
+}
+
+// every band with a center in the audible range, exact centers as IEC 61260-1 defines them
+fn octave_bands(fraction: u8) -> Vec<Band> {
+    let fraction = fraction as f32;
+    let half_width = OCTAVE_RATIO.powf(1f32 / (2f32 * fraction));
+
+    // odd fractions have a band centered on the reference, even ones have an edge there
+    let center = |index: i32| {
+        let exponent = if fraction as i32 % 2 == 1 {
+            index as f32 / fraction
+        } else {
+            (2 * index + 1) as f32 / (2f32 * fraction)
+        };
+        REFERENCE_FREQUENCY * OCTAVE_RATIO.powf(exponent)
+    };
+
+    let lowest =
+        (fraction * (MIN_FREQUENCY / REFERENCE_FREQUENCY).log(OCTAVE_RATIO)).floor() as i32 - 1;
+    let highest =
+        (fraction * (MAX_FREQUENCY / REFERENCE_FREQUENCY).log(OCTAVE_RATIO)).ceil() as i32 + 1;
+
+    (lowest..=highest)
+        .map(|index| {
+            let center = center(index);
+            Band {
+                low: center / half_width,
+                center,
+                high: center * half_width,
+            }
+        })
+        // nominal centers get rounded, so 20 Hz and 20 kHz bands are in even though they're a hair off
+        .filter(|band| {
+            band.center >= MIN_FREQUENCY / 1.01f32 && band.center <= MAX_FREQUENCY * 1.01f32
+        })
+        .collect()
+}
+
+// triangles that each reach from the center of the one below to the center of the one above
+fn mel_bands(count: usize) -> Vec<Band> {
+    let (min_mel, max_mel) = (to_mel(MIN_FREQUENCY), to_mel(MAX_FREQUENCY));
+    // count + 2 points, the outermost ones are only edges
+    let point =
+        |index: usize| from_mel(min_mel + (max_mel - min_mel) * index as f32 / (count + 1) as f32);
+
+    (0..count)
+        .map(|index| Band {
+            low: point(index),
+            center: point(index + 1),
+            high: point(index + 2),
+        })
+        .collect()
+}
+
+fn bark_bands() -> Vec<Band> {
+    BARK_EDGES
+        .windows(2)
+        .map(|edges| {
+            let (low, high) = (edges[0], edges[1]);
+            Band {
+                low,
+                center: (low * high).sqrt(),
+                high,
+            }
+        })
+        .collect()
+}
+
+// O'Shaughnessy's formula, the one HTK and most feature extractors use
+fn to_mel(frequency: f32) -> f32 {
+    2595f32 * (1f32 + frequency / 700f32).log10()
+}
+
+fn from_mel(mel: f32) -> f32 {
+    700f32 * (10f32.powf(mel / 2595f32) - 1f32)
+}
+
+// loudest bin in each band, bands too narrow to hold a bin get the spectrum at their center
+fn aggregate_peaks(magnitudes: &[f32], bin_width: f32, bands: &[Band]) -> Vec<f32> {
+    bands
+        .iter()
+        .map(|band| {
+            let first_bin = (band.low / bin_width).ceil() as usize;
+            let end_bin = ((band.high / bin_width).ceil() as usize).min(magnitudes.len());
+
+            if first_bin < end_bin {
+                magnitudes[first_bin..end_bin]
+                    .iter()
+                    .fold(0f32, |peak, magnitude| peak.max(*magnitude))
+            } else {
+                interpolate_bins(magnitudes, band.center / bin_width)
+            }
+        })
+        .collect()
+}
+
+// all the energy in each band, bins that straddle an edge count for the part inside
+fn aggregate_energy(magnitudes: &[f32], bin_width: f32, bands: &[Band]) -> Vec<f32> {
+    bands
+        .iter()
+        .map(|band| {
+            // bin k covers k - 0.5 to k + 0.5 bin widths
+            let low = band.low / bin_width + 0.5f32;
+            let high = (band.high / bin_width + 0.5f32).min(magnitudes.len() as f32);
+
+            let mut energy = 0f32;
+            let mut position = low;
+            while position < high {
+                let bin = position as usize;
+                let next = (bin + 1) as f32;
+                let overlap = next.min(high) - position;
+
+                energy += magnitudes[bin] * magnitudes[bin] * overlap;
+                position = next;
+            }
+
+            energy.sqrt()
+        })
+        .collect()
+}
+
+// energy through each band's triangular filter, filters too narrow to hold a bin
+// get the spectrum at their center like aggregate_peaks does
+fn aggregate_triangles(magnitudes: &[f32], bin_width: f32, bands: &[Band]) -> Vec<f32> {
+    bands
+        .iter()
+        .map(|band| {
+            let first_bin = (band.low / bin_width).ceil() as usize;
+            let end_bin = ((band.high / bin_width).ceil() as usize).min(magnitudes.len());
+
+            if first_bin >= end_bin {
+                return interpolate_bins(magnitudes, band.center / bin_width);
+            }
+
+            // the filter peaks at 1, so a tone at its center reads like in an octave band
+            let energy = (first_bin..end_bin).fold(0f32, |energy, bin| {
+                let frequency = bin as f32 * bin_width;
+                let weight = if frequency < band.center {
+                    (frequency - band.low) / (band.center - band.low)
+                } else {
+                    (band.high - frequency) / (band.high - band.center)
+                };
+                energy + magnitudes[bin] * magnitudes[bin] * weight
+            });
+
+            energy.sqrt()
+        })
+        .collect()
+}
+
+// the bands that land in one of `num_slots` equal slices of a display, at least one each
+pub fn slot_bands(slot: usize, num_slots: usize, num_bands: usize) -> Range<usize> {
+    let start = slot * num_bands / num_slots;
+    let end = ((slot + 1) * num_bands / num_slots).max(start + 1);
+    start.min(num_bands)..end.min(num_bands)
+}
+
+// linear interpolation between the two bins around a fractional bin position
+fn interpolate_bins(data: &[f32], position: f32) -> f32 {
+    let index = position as usize;
+    let fraction = position - index as f32;
+
+    match (data.get(index), data.get(index + 1)) {
+        (Some(low), Some(high)) => low + (high - low) * fraction,
+        (Some(low), None) => *low,
+        _ => 0f32,
+    }
+}
+
+// closest ISO 266 preferred number
+fn nominal(frequency: f32) -> f32 {
+    let decade = 10f32.powf(frequency.log10().floor());
+    let mantissa = frequency / decade;
+
+    let closest = PREFERRED_NUMBERS
+        .iter()
+        .chain(&[10f32])
+        .fold(1f32, |closest, candidate| {
+            if (candidate / mantissa).ln().abs() < (closest / mantissa).ln().abs() {
+                *candidate
+            } else {
+                closest
+            }
+        });
+
+    closest * decade
+}
+
+fn round_to_digits(value: f32, digits: i32) -> f32 {
+    let scale = 10f32.powi(digits - 1 - value.log10().floor() as i32);
+    (value * scale).round() / scale
+}
+
+// 31.5, 125, 1k, 1.25k and so on
+fn format_frequency(frequency: f32) -> String {
+    let (value, suffix) = if frequency >= 1000f32 {
+        (frequency / 1000f32, "k")
+    } else {
+        (frequency, "")
+    };
+
+    // round away float noise before trimming zeros
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    format!("{}{}", text, suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} isn't within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn log_bands_cover_the_audible_range() {
+        let bands = log_bands(30);
+
+        assert_eq!(bands.len(), 30);
+        assert_close(bands[0].low, MIN_FREQUENCY, 1e-3);
+        assert_close(bands[29].high, MAX_FREQUENCY, 0.5);
+        for pair in bands.windows(2) {
+            assert_close(pair[0].high, pair[1].low, 1e-2);
+            assert_close(
+                pair[1].center / pair[0].center,
+                1000f32.powf(1f32 / 30f32),
+                1e-4,
+            );
+        }
+    }
+
+    #[test]
+    fn slot_bands_split_evenly() {
+        assert_eq!(slot_bands(0, 4, 8), 0..2);
+        assert_eq!(slot_bands(3, 4, 8), 6..8);
+    }
+
+    #[test]
+    fn slot_bands_give_every_slot_a_band() {
+        // more slots than bands, neighbours share
+        assert_eq!(slot_bands(0, 8, 4), 0..1);
+        assert_eq!(slot_bands(1, 8, 4), 0..1);
+        assert_eq!(slot_bands(7, 8, 4), 3..4);
+    }
+
+    #[test]
+    fn third_octave_centers_are_exact() {
+        let bands = octave_bands(3);
+
+        // IEC 61260-1 exact centers, 10^(n/10) from 20 Hz to 20 kHz
+        assert_eq!(bands.len(), 31);
+        assert_close(bands[0].center, 19.953, 1e-3);
+        assert_close(bands[17].center, 1000f32, 1e-3);
+        assert_close(bands[30].center, 19953f32, 1f32);
+        for pair in bands.windows(2) {
+            assert_close(pair[1].center / pair[0].center, 1.258_925_4, 1e-5);
+        }
+    }
+
+    #[test]
+    fn octave_edges_meet() {
+        let bands = octave_bands(1);
+
+        assert_eq!(bands.len(), 10);
+        assert_close(bands[5].low, 707.95, 1e-2);
+        assert_close(bands[5].high, 1412.5, 1e-1);
+        for pair in bands.windows(2) {
+            assert_close(pair[0].high, pair[1].low, 1e-2);
+        }
+    }
+
+    #[test]
+    fn even_fractions_have_an_edge_at_the_reference() {
+        let bands = octave_bands(6);
+
+        assert!(bands
+            .iter()
+            .any(|band| (band.high - REFERENCE_FREQUENCY).abs() < 1e-2));
+        assert!(bands
+            .iter()
+            .all(|band| (band.center - REFERENCE_FREQUENCY).abs() > 1f32));
+    }
+
+    #[test]
+    fn nominal_rounds_to_preferred_numbers() {
+        assert_eq!(nominal(19.953), 20f32);
+        assert_eq!(nominal(31.623), 31.5);
+        assert_eq!(nominal(63.096), 63f32);
+        assert_eq!(nominal(1258.9), 1250f32);
+        assert_eq!(nominal(9.999), 10f32);
+        assert_eq!(nominal(15849f32), 16000f32);
+    }
+
+    #[test]
+    fn third_octave_labels_are_nominal() {
+        let scale = BandScale::Octave(3);
+        let labels: Vec<String> = scale
+            .bands(0)
+            .iter()
+            .map(|band| scale.label(band))
+            .collect();
+
+        assert_eq!(
+            labels,
+            [
+                "20", "25", "31.5", "40", "50", "63", "80", "100", "125", "160", "200", "250",
+                "315", "400", "500", "630", "800", "1k", "1.25k", "1.6k", "2k", "2.5k", "3.15k",
+                "4k", "5k", "6.3k", "8k", "10k", "12.5k", "16k", "20k",
+            ]
+        );
+    }
+
+    #[test]
+    fn octave_labels_are_nominal() {
+        let scale = BandScale::Octave(1);
+        let labels: Vec<String> = scale
+            .bands(0)
+            .iter()
+            .map(|band| scale.label(band))
+            .collect();
+
+        assert_eq!(
+            labels,
+            ["31.5", "63", "125", "250", "500", "1k", "2k", "4k", "8k", "16k"]
+        );
+    }
+
+    #[test]
+    fn other_labels_are_rounded_to_three_digits() {
+        let band = Band {
+            low: 0f32,
+            center: 1234.5,
+            high: 0f32,
+        };
+
+        assert_eq!(BandScale::Log.label(&band), "1.23k");
+        assert_eq!(format_frequency(440f32), "440");
+        assert_eq!(format_frequency(round_to_digits(86.13, 3)), "86.1");
+    }
+
+    #[test]
+    fn mel_conversions_match_htk() {
+        assert_close(to_mel(0f32), 0f32, 1e-3);
+        assert_close(to_mel(700f32), 781.17, 1e-2);
+        assert_close(to_mel(1000f32), 1000f32, 0.1);
+        assert_close(to_mel(8000f32), 2840.02, 0.1);
+        for frequency in [20f32, 440f32, 1000f32, 15000f32] {
+            assert_close(from_mel(to_mel(frequency)), frequency, frequency * 1e-4);
+        }
+    }
+
+    #[test]
+    fn mel_triangles_reach_to_their_neighbours_centers() {
+        let bands = mel_bands(40);
+
+        assert_eq!(bands.len(), 40);
+        assert_close(bands[0].low, MIN_FREQUENCY, 1e-2);
+        assert_close(bands[39].high, MAX_FREQUENCY, 1f32);
+        for pair in bands.windows(2) {
+            assert_close(pair[0].center, pair[1].low, 1e-2);
+            assert_close(pair[0].high, pair[1].center, 1e-2);
+            assert_close(
+                to_mel(pair[1].center) - to_mel(pair[0].center),
+                to_mel(pair[0].center) - to_mel(pair[0].low),
+                1e-1,
+            );
+        }
+    }
+
+    #[test]
+    fn bark_bands_follow_zwicker() {
+        let bands = bark_bands();
+
+        assert_eq!(bands.len(), 24);
+        assert_close(bands[0].low, 20f32, 1e-3);
+        assert_close(bands[0].high, 100f32, 1e-3);
+        // band 9 is the one around 1 kHz
+        assert_close(bands[8].low, 920f32, 1e-3);
+        assert_close(bands[8].high, 1080f32, 1e-3);
+        assert_close(bands[23].low, 12000f32, 1e-3);
+        assert_close(bands[23].high, 15500f32, 1e-3);
+        for pair in bands.windows(2) {
+            assert_eq!(pair[0].high, pair[1].low);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::str::FromStr;
+
+// every curve is 0 dB here, pink compensation tilts around it
+pub const REFERENCE_FREQUENCY: f32 = 1000f32;
+
+// how much each frequency gets boosted or cut before the magnitudes are scaled
+#[derive(Debug, Clone, Copy)]
+pub enum Weighting {
+    // the original hand tuned tilt, only the pretty curve uses it
+    Tilt,
+    // IEC 61672-1, roughly how loud quiet sounds seem
+    A,
+    // IEC 61672-1, roughly how loud loud sounds seem
+    C,
+    // flat, the spectrum as measured
+    Z,
+    // ITU-R BS.468-4, how annoying noise is
+    Itu468,
+    // a constant slope in dB per octave, +3 makes pink noise flat
+    Pink,
+}
+
+impl Weighting {
+    pub fn next(self) -> Self {
+        match self {
+            Weighting::Tilt => Weighting::A,
+            Weighting::A => Weighting::C,
+            Weighting::C => Weighting::Z,
+            Weighting::Z => Weighting::Itu468,
+            Weighting::Itu468 => Weighting::Pink,
+            Weighting::Pink => Weighting::Tilt,
+        }
+    }
+
+    // gain at a frequency in dB, 0 at 1 kHz for all of them
+    pub fn gain_db(self, frequency: f32, pink_slope: f32) -> f32 {
+        match self {
+            Weighting::Tilt | Weighting::Z => 0f32,
+            Weighting::A => a_weighting(frequency),
+            Weighting::C => c_weighting(frequency),
+            Weighting::Itu468 => itu_468_weighting(frequency),
+            Weighting::Pink => pink_slope * (frequency / REFERENCE_FREQUENCY).log2(),
+        }
+    }
+}
+
+impl fmt::Display for Weighting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Weighting::Tilt => "tilt",
+            Weighting::A => "a",
+            Weighting::C => "c",
+            Weighting::Z => "z",
+            Weighting::Itu468 => "itu-r-468",
+            Weighting::Pink => "pink",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Weighting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tilt" => Ok(Weighting::Tilt),
+            "a" => Ok(Weighting::A),
+            "c" => Ok(Weighting::C),
+            "z" => Ok(Weighting::Z),
+            "itu-r-468" | "468" => Ok(Weighting::Itu468),
+            "pink" => Ok(Weighting::Pink),
+            _ => Err(format!("unknown weighting: {}", s)),
+        }
+    }
+}
+
+// the pole frequencies of IEC 61672-1, squared
+const POLE_20_6: f64 = 20.6 * 20.6;
+const POLE_107_7: f64 = 107.7 * 107.7;
+const POLE_737_9: f64 = 737.9 * 737.9;
+const POLE_12194: f64 = 12194.0 * 12194.0;
+
+fn a_weighting(frequency: f32) -> f32 {
+    let f2 = (frequency as f64).powi(2);
+    let response = POLE_12194 * f2 * f2
+        / ((f2 + POLE_20_6) * ((f2 + POLE_107_7) * (f2 + POLE_737_9)).sqrt() * (f2 + POLE_12194));
+
+    // the offset brings 1 kHz to 0 dB
+    (20.0 * response.log10() + 2.0) as f32
+}
+
+fn c_weighting(frequency: f32) -> f32 {
+    let f2 = (frequency as f64).powi(2);
+    let response = POLE_12194 * f2 / ((f2 + POLE_20_6) * (f2 + POLE_12194));
+
+    (20.0 * response.log10() + 0.062) as f32
+}
+
+// the standard's closed form, in f64 since the sixth power of 20 kHz eats all of an f32's precision
+fn itu_468_weighting(frequency: f32) -> f32 {
+    let f = frequency as f64;
+    let h1 = -4.737_338_981_378_384e-24 * f.powi(6) + 2.043_828_333_606_125e-15 * f.powi(4)
+        - 1.363_894_795_463_638e-7 * f.powi(2)
+        + 1.0;
+    let h2 = 1.306_612_257_412_824e-19 * f.powi(5) - 2.118_150_887_518_656e-11 * f.powi(3)
+        + 5.559_488_023_498_642e-4 * f;
+    let response = 1.246_332_637_532_143e-4 * f / (h1 * h1 + h2 * h2).sqrt();
+
+    // 0 dB at 1 kHz, which leaves the peak at +12.2 dB around 6.3 kHz
+    (18.2 + 20.0 * response.log10()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // published values are rounded to 0.1 dB
+    fn assert_db(weighting: Weighting, frequency: f32, expected: f32) {
+        let gain = weighting.gain_db(frequency, 3f32);
+        assert!(
+            (gain - expected).abs() <= 0.1,
+            "{} at {} Hz is {} dB, not {} dB",
+            weighting,
+            frequency,
+            gain,
+            expected
+        );
+    }
+
+    #[test]
+    fn a_weighting_matches_iec_61672() {
+        // the table's 31.5 Hz is the base ten band at 10^1.5
+        assert_db(Weighting::A, 31.623, -39.4);
+        assert_db(Weighting::A, 100f32, -19.1);
+        assert_db(Weighting::A, 1000f32, 0f32);
+        assert_db(Weighting::A, 2000f32, 1.2);
+        assert_db(Weighting::A, 10000f32, -2.5);
+    }
+
+    #[test]
+    fn c_weighting_matches_iec_61672() {
+        assert_db(Weighting::C, 31.5, -3f32);
+        assert_db(Weighting::C, 100f32, -0.3);
+        assert_db(Weighting::C, 1000f32, 0f32);
+        assert_db(Weighting::C, 10000f32, -4.4);
+    }
+
+    #[test]
+    fn itu_468_weighting_matches_bs_468() {
+        assert_db(Weighting::Itu468, 31.5, -29.9);
+        assert_db(Weighting::Itu468, 1000f32, 0f32);
+        assert_db(Weighting::Itu468, 6300f32, 12.2);
+        assert_db(Weighting::Itu468, 20000f32, -22.2);
+    }
+
+    #[test]
+    fn pink_tilts_around_the_reference() {
+        assert_db(Weighting::Pink, REFERENCE_FREQUENCY, 0f32);
+        assert_db(Weighting::Pink, 2000f32, 3f32);
+        assert_db(Weighting::Pink, 250f32, -6f32);
+        assert_eq!(Weighting::Pink.gain_db(4000f32, 4.5), 9f32);
+    }
+
+    #[test]
+    fn tilt_and_z_are_flat() {
+        assert_db(Weighting::Tilt, 50f32, 0f32);
+        assert_db(Weighting::Z, 15000f32, 0f32);
+    }
+}
@@ -0,0 +1,2 @@
+// the parts that run on the audio thread, in a library so the benchmarks can use them too
+pub mod sample_queue;
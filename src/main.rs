@@ -1,36 +1,103 @@
-use structopt::StructOpt;
+use structopt::{clap, StructOpt};
+
+use cpal::HostId;
 
 use iced::{
     executor, keyboard,
-    theme::Palette,
+    theme::{self, Palette},
     time,
-    widget::{button, container, text, Column},
+    widget::{button, container, text, text_input, Column, Row},
     window, Alignment, Application, Color, Command, Element, Settings, Subscription, Theme,
 };
-use iced_native::subscription;
+use iced_native::{event, subscription};
 
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Duration;
 
-use cpal::traits::DeviceTrait;
+mod fft_window;
+use fft_window::WindowFunction;
+
+mod frequency_bands;
+use frequency_bands::{BandScale, MAX_BANDS};
+
+mod frequency_weighting;
+use frequency_weighting::Weighting;
+
+mod sound_analyzer;
+use sound_analyzer::{AnalysisSettings, MAX_HOP_SIZE, MIN_HOP_SIZE};
+
+mod sound_file;
+
+mod sound_generator;
+use sound_generator::{Tone, GENERATOR_SAMPLE_RATE};
 
 mod sound_proxy;
-use sound_proxy::SoundProxy;
+use sound_proxy::{SoundProxy, SoundProxyError, MAX_CLIP_SIZE, MIN_CLIP_SIZE};
 
 mod sound_transformer;
+use sound_transformer::{MagnitudeScale, SoundTransformer};
 
 mod spectrum_visualization;
-use spectrum_visualization::{Visualizer, VisualizerMessage};
+use spectrum_visualization::{ColorMap, Visualizer, VisualizerMessage, MAX_HISTORY};
 
 enum AppState {
     SelectingSource,
     Displaying,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum DisplayType {
     Lines,
     Boxes,
     Circle,
+    Waterfall,
+    Bars,
+    Goniometer,
+}
+
+impl DisplayType {
+    fn next(self) -> Self {
+        match self {
+            DisplayType::Lines => DisplayType::Boxes,
+            DisplayType::Boxes => DisplayType::Circle,
+            DisplayType::Circle => DisplayType::Waterfall,
+            DisplayType::Waterfall => DisplayType::Bars,
+            DisplayType::Bars => DisplayType::Goniometer,
+            DisplayType::Goniometer => DisplayType::Lines,
+        }
+    }
+}
+
+impl fmt::Display for DisplayType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DisplayType::Lines => "lines",
+            DisplayType::Boxes => "boxes",
+            DisplayType::Circle => "circle",
+            DisplayType::Waterfall => "waterfall",
+            DisplayType::Bars => "bars",
+            DisplayType::Goniometer => "goniometer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DisplayType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lines" => Ok(DisplayType::Lines),
+            "boxes" => Ok(DisplayType::Boxes),
+            "circle" => Ok(DisplayType::Circle),
+            "waterfall" => Ok(DisplayType::Waterfall),
+            "bars" => Ok(DisplayType::Bars),
+            "goniometer" => Ok(DisplayType::Goniometer),
+            _ => Err(format!("unknown display type: {}", s)),
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -39,7 +106,7 @@ pub enum ContentType {
     Processed,
 }
 
-#[derive(Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Sides<T> {
     left: T,
     right: T,
@@ -49,9 +116,24 @@ pub struct Sides<T> {
 pub enum AppMessage {
     Quit,
     ScanDevices,
-    SelectDevice(usize),
-    UnselectDevice,
+    PollDevices,
+    SelectHost(HostId),
+    ShiftChannelPair(i32),
+    ShiftClipSize(i32),
+    SelectSource(String),
+    UnselectSource,
+    FilePathChanged(String),
+    OpenFile,
+    SourceFailed(String),
     SwitchDisplayContent,
+    CycleDisplayType,
+    SetDisplayType(DisplayType),
+    CycleColorMap,
+    CycleWindow,
+    CycleBandScale,
+    CycleMagnitudeScale,
+    CycleWeighting,
+    ToggleTrigger,
     ToggleNormalize,
     ToggleSmooth,
     ToggleFlashFlood,
@@ -64,12 +146,17 @@ pub enum AppMessage {
 
 struct App {
     debug: bool,
+    scan_interval: u64,
 
     should_exit: bool,
     state: AppState,
     sound_proxy: SoundProxy,
     visualizer: Visualizer,
 
+    file_path: String,
+    // shown on the source selection screen until the next source works out
+    error: Option<String>,
+
     palette: Palette,
 }
 
@@ -80,31 +167,70 @@ impl Application for App {
     type Theme = Theme;
 
     fn new(flags: Self::Flags) -> (Self, Command<Self::Message>) {
-        (
-            Self {
-                debug: flags.debug,
-
-                should_exit: false,
-                state: AppState::SelectingSource,
-                visualizer: Visualizer::new(
-                    flags.width,
-                    flags.height,
-                    ContentType::Processed,
-                    DisplayType::Lines,
-                    true,
-                ),
-                sound_proxy: SoundProxy::default(),
-
-                palette: Palette {
-                    background: Color::from_rgb8(0x33, 0x33, 0x33),
-                    text: Color::from_rgb8(0xff, 0xff, 0xff),
-                    primary: Color::from_rgb8(0xff, 0xff, 0),
-                    success: Color::from_rgb8(0, 0xff, 0),
-                    danger: Color::from_rgb8(0xff, 0, 0),
-                },
+        let mut app = Self {
+            debug: flags.debug,
+            scan_interval: flags.scan_interval,
+
+            should_exit: false,
+            state: AppState::SelectingSource,
+            visualizer: Visualizer::new(&flags),
+            sound_proxy: SoundProxy::default(),
+
+            file_path: String::new(),
+            error: None,
+
+            palette: Palette {
+                background: Color::from_rgb8(0x33, 0x33, 0x33),
+                text: Color::from_rgb8(0xff, 0xff, 0xff),
+                primary: Color::from_rgb8(0xff, 0xff, 0),
+                success: Color::from_rgb8(0, 0xff, 0),
+                danger: Color::from_rgb8(0xff, 0, 0),
+            },
+        };
+
+        if let Err(error) = app.sound_proxy.set_host(flags.host) {
+            app.fail(error.to_string());
+        }
+        if let Err(error) = app.sound_proxy.scan_devices() {
+            app.fail(error.to_string());
+        }
+        app.sound_proxy.set_channel_pair(flags.channels.clone());
+        app.sound_proxy.set_sample_rate(flags.sample_rate);
+        let mut transformer = SoundTransformer::default();
+        transformer.set_magnitude_scale(flags.magnitude);
+        transformer.set_db_range(flags.min_db, flags.max_db);
+        transformer.set_weighting(flags.weighting);
+        transformer.set_pink_slope(flags.pink_slope);
+        app.sound_proxy.set_analysis_settings(AnalysisSettings {
+            clip_size: flags.fft_size,
+            hop_size: flags.hop_size,
+            window: flags.window,
+            band_scale: flags.scale,
+            num_bands: flags.bands,
+            transformer,
+        });
+        app.sound_proxy.add_generators(Sides {
+            left: Tone {
+                frequency: flags.left_frequency,
+                amplitude: flags.left_amplitude,
             },
-            Command::none(),
-        )
+            right: Tone {
+                frequency: flags.right_frequency,
+                amplitude: flags.right_amplitude,
+            },
+        });
+
+        if let Some(path) = flags.file {
+            app.file_path = path.display().to_string();
+            app.open_file();
+        } else if let Some(query) = flags.device {
+            match app.sound_proxy.find_device(&query) {
+                Some(index) => app.select_source(index),
+                None => app.fail(format!("no device matches {}", query)),
+            }
+        }
+
+        (app, Command::none())
     }
 
     fn title(&self) -> String {
@@ -120,7 +246,9 @@ impl Application for App {
     }
 
     fn subscription(&self) -> Subscription<Self::Message> {
-        let events = subscription::events_with(|event, _status| match event {
+        let events = subscription::events_with(|event, status| match event {
+            // keys typed into a text input aren't shortcuts
+            iced_native::Event::Keyboard(_) if status == event::Status::Captured => None,
             iced_native::Event::Keyboard(keyboard_event) => {
                 match keyboard_event {
                     keyboard::Event::KeyPressed {
@@ -133,16 +261,91 @@ impl Application for App {
                         ..
                     } => Some(AppMessage::ScanDevices),
 
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::RBracket,
+                        ..
+                    } => Some(AppMessage::ShiftChannelPair(1)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::LBracket,
+                        ..
+                    } => Some(AppMessage::ShiftChannelPair(-1)),
+
                     keyboard::Event::KeyPressed {
                         key_code: keyboard::KeyCode::M,
                         ..
-                    } => Some(AppMessage::UnselectDevice),
+                    } => Some(AppMessage::UnselectSource),
 
                     keyboard::Event::KeyPressed {
                         key_code: keyboard::KeyCode::P,
                         ..
                     } => Some(AppMessage::SwitchDisplayContent),
 
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::D,
+                        ..
+                    } => Some(AppMessage::CycleDisplayType),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key1,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Lines)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key2,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Boxes)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key3,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Circle)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key4,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Waterfall)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key5,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Bars)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Key6,
+                        ..
+                    } => Some(AppMessage::SetDisplayType(DisplayType::Goniometer)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::B,
+                        ..
+                    } => Some(AppMessage::CycleBandScale),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::A,
+                        ..
+                    } => Some(AppMessage::CycleMagnitudeScale),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::G,
+                        ..
+                    } => Some(AppMessage::CycleWeighting),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::C,
+                        ..
+                    } => Some(AppMessage::CycleColorMap),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::W,
+                        ..
+                    } => Some(AppMessage::CycleWindow),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::T,
+                        ..
+                    } => Some(AppMessage::ToggleTrigger),
+
                     keyboard::Event::KeyPressed {
                         key_code: keyboard::KeyCode::N,
                         ..
@@ -168,6 +371,16 @@ impl Application for App {
                         ..
                     } => Some(AppMessage::ShiftMovingAvgRange(-1)),
 
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Equals, // +
+                        ..
+                    } => Some(AppMessage::ShiftClipSize(1)),
+
+                    keyboard::Event::KeyPressed {
+                        key_code: keyboard::KeyCode::Minus,
+                        ..
+                    } => Some(AppMessage::ShiftClipSize(-1)),
+
                     keyboard::Event::KeyPressed {
                         key_code: keyboard::KeyCode::O,
                         ..
@@ -195,13 +408,20 @@ impl Application for App {
             Subscription::none()
         };
 
-        Subscription::batch(vec![events, ticks])
+        // catches devices being plugged in or out
+        let polls = if self.scan_interval > 0 {
+            time::every(Duration::from_secs(self.scan_interval)).map(|_| AppMessage::PollDevices)
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch(vec![events, ticks, polls])
     }
 
     fn update(&mut self, message: Self::Message) -> iced::Command<Self::Message> {
         if self.debug {
-            if let AppMessage::Tick = message {
-                // don't print for ticks, that would clog the console
+            if let AppMessage::Tick | AppMessage::PollDevices = message {
+                // don't print for ticks or polls, that would clog the console
             } else {
                 println!("Message: {:?}", message)
             }
@@ -212,42 +432,107 @@ impl Application for App {
                 self.should_exit = true;
             }
             AppMessage::ScanDevices => {
-                self.sound_proxy.scan_devices();
+                if let Err(error) = self.sound_proxy.scan_devices() {
+                    self.fail(error.to_string());
+                }
             }
-            AppMessage::SelectDevice(index) => {
-                self.state = AppState::Displaying;
-                self.sound_proxy.select_device(index);
+            // whatever's playing only has to stop if it was the device that went away
+            AppMessage::PollDevices => match self.sound_proxy.poll_devices() {
+                Ok(()) => {}
+                Err(error @ SoundProxyError::Disconnected(_)) => self.fail(error.to_string()),
+                Err(error) => eprintln!("{}", error),
+            },
+            AppMessage::SelectHost(host_id) => {
+                self.error = None;
+                if let Err(error) = self
+                    .sound_proxy
+                    .set_host(host_id)
+                    .and_then(|_| self.sound_proxy.scan_devices())
+                {
+                    self.fail(error.to_string());
+                }
+            }
+            AppMessage::ShiftChannelPair(step) => {
+                if let Err(error) = self.sound_proxy.shift_channel_pair(step) {
+                    self.fail(error.to_string());
+                }
             }
-            AppMessage::UnselectDevice => {
+            AppMessage::ShiftClipSize(step) => self.sound_proxy.shift_clip_size(step),
+            AppMessage::SelectSource(name) => match self.sound_proxy.find_source(&name) {
+                Some(index) => self.select_source(index),
+                None => self.fail(format!("{} is gone", name)),
+            },
+            AppMessage::UnselectSource => {
                 self.state = AppState::SelectingSource;
-                self.sound_proxy.unselect_device();
+                self.sound_proxy.unselect_source();
+            }
+            AppMessage::FilePathChanged(path) => {
+                self.file_path = path;
             }
+            AppMessage::OpenFile => self.open_file(),
+            AppMessage::SourceFailed(error) => self.fail(error),
 
             // pass through to the visualizer
             AppMessage::SwitchDisplayContent => self
                 .visualizer
                 .update(VisualizerMessage::SwitchDisplayContent),
-            AppMessage::ToggleNormalize => {
-                self.visualizer.update(VisualizerMessage::ToggleNormalize)
+            AppMessage::CycleDisplayType => {
+                self.visualizer.update(VisualizerMessage::CycleDisplayType)
             }
-            AppMessage::ToggleSmooth => self.visualizer.update(VisualizerMessage::ToggleSmooth),
-            AppMessage::ToggleFlashFlood => {
-                self.visualizer.update(VisualizerMessage::ToggleFlashFlood)
-            }
-            AppMessage::ShiftMovingAvgRange(val) => self
+            AppMessage::SetDisplayType(display_type) => self
                 .visualizer
-                .update(VisualizerMessage::ShiftMovingAvgRange(val)),
-            AppMessage::ScaleUp => self.visualizer.update(VisualizerMessage::ScaleUp),
-            AppMessage::ScaleDown => self.visualizer.update(VisualizerMessage::ScaleDown),
+                .update(VisualizerMessage::SetDisplayType(display_type)),
+            AppMessage::CycleColorMap => self.visualizer.update(VisualizerMessage::CycleColorMap),
+            AppMessage::CycleWindow => self.sound_proxy.cycle_window(),
+            AppMessage::CycleBandScale => self.sound_proxy.cycle_band_scale(),
+            AppMessage::CycleMagnitudeScale => self.sound_proxy.cycle_magnitude_scale(),
+            AppMessage::CycleWeighting => self.sound_proxy.cycle_weighting(),
+            AppMessage::ToggleTrigger => self.visualizer.update(VisualizerMessage::ToggleTrigger),
+            AppMessage::ToggleNormalize => self
+                .sound_proxy
+                .update_transformer(SoundTransformer::toggle_norm),
+            AppMessage::ToggleSmooth => self
+                .sound_proxy
+                .update_transformer(SoundTransformer::toggle_smooth),
+            AppMessage::ToggleFlashFlood => self
+                .sound_proxy
+                .update_transformer(SoundTransformer::toggle_flash_flood),
+            AppMessage::ShiftMovingAvgRange(val) => {
+                let debug = self.debug;
+                self.sound_proxy.update_transformer(|transformer| {
+                    transformer.shift_moving_avg_range(val, debug)
+                })
+            }
+            // zooms whatever is on screen, samples or the spectrum, not both
+            AppMessage::ScaleUp => {
+                if self.visualizer.shows_samples() {
+                    self.visualizer.update(VisualizerMessage::ScaleUp)
+                } else {
+                    self.sound_proxy
+                        .update_transformer(|transformer| transformer.shift_norm_scale(1.15f32))
+                }
+            }
+            AppMessage::ScaleDown => {
+                if self.visualizer.shows_samples() {
+                    self.visualizer.update(VisualizerMessage::ScaleDown)
+                } else {
+                    self.sound_proxy.update_transformer(|transformer| {
+                        transformer.shift_norm_scale(1f32 / 1.15f32)
+                    })
+                }
+            }
             AppMessage::ToggleOffCenter => {
                 self.visualizer.update(VisualizerMessage::ToggleOffCenter)
             }
             AppMessage::Tick => {
+                if let Some(error) = self.sound_proxy.take_stream_error() {
+                    return self.update(AppMessage::SourceFailed(error.to_string()));
+                }
+
                 if let AppState::Displaying = self.state {
-                    self.visualizer
-                        .update(VisualizerMessage::UpdateContent(Box::new(
-                            self.sound_proxy.get_clip(),
-                        )));
+                    self.visualizer.update(VisualizerMessage::UpdateContent(
+                        self.sound_proxy.take_frames(),
+                    ));
                 }
             }
         }
@@ -255,28 +540,101 @@ impl Application for App {
         Command::none()
     }
 
-    fn view(&self) -> Element<Self::Message> {
+    fn view(&self) -> Element<'_, Self::Message> {
         match self.state {
             AppState::SelectingSource => {
-                let devices = self.sound_proxy.get_devices();
+                let sources = self.sound_proxy.get_sources();
+
+                // the current host is highlighted
+                let current_host = self.sound_proxy.get_host_id();
+                let hosts = self.sound_proxy.get_hosts().into_iter().fold(
+                    Row::new().spacing(10).push(text("Host:")),
+                    |row, host_id| {
+                        let style = if host_id == current_host {
+                            theme::Button::Primary
+                        } else {
+                            theme::Button::Secondary
+                        };
+                        row.push(
+                            button(text(host_id.name()))
+                                .style(style)
+                                .on_press(AppMessage::SelectHost(host_id)),
+                        )
+                    },
+                );
 
-                let buttons = devices.iter().enumerate().fold(
+                let buttons = sources.iter().fold(
                     Column::new().align_items(Alignment::Start),
-                    |column, (i, device)| {
+                    |column, source| {
                         column.push(
-                            button(text(device.name().expect("device name")))
-                                .on_press(AppMessage::SelectDevice(i)),
+                            button(text(source.name()))
+                                .on_press(AppMessage::SelectSource(source.name())),
                         )
                     },
                 );
 
-                container(buttons).into()
+                let file_input = Row::new()
+                    .spacing(10)
+                    .push(
+                        text_input(
+                            "path to a sound file",
+                            &self.file_path,
+                            AppMessage::FilePathChanged,
+                        )
+                        .on_submit(AppMessage::OpenFile),
+                    )
+                    .push(button(text("Open")).on_press(AppMessage::OpenFile));
+
+                let error_banner = self.error.as_ref().map(|error| {
+                    text(format!("Error: {}", error))
+                        .style(self.palette.danger)
+                        .size(20)
+                });
+
+                let column = match error_banner {
+                    Some(banner) => Column::new().push(banner),
+                    None => Column::new(),
+                }
+                .spacing(10)
+                .push(hosts)
+                .push(buttons.spacing(10))
+                .push(file_input);
+
+                container(column).padding(10).into()
             }
             AppState::Displaying => self.visualizer.view(),
         }
     }
 }
 
+impl App {
+    fn select_source(&mut self, index: usize) {
+        match self.sound_proxy.select_source(index) {
+            Ok(()) => {
+                self.state = AppState::Displaying;
+                self.error = None;
+            }
+            Err(error) => self.fail(error.to_string()),
+        }
+    }
+
+    fn open_file(&mut self) {
+        match self.sound_proxy.add_file(PathBuf::from(&self.file_path)) {
+            Ok(index) => self.select_source(index),
+            Err(error) => self.fail(format!("{}: {}", self.file_path, error)),
+        }
+    }
+
+    // drop whatever was playing and go back to picking a source
+    fn fail(&mut self, error: String) {
+        eprintln!("{}", error);
+
+        self.state = AppState::SelectingSource;
+        self.sound_proxy.unselect_source();
+        self.error = Some(error);
+    }
+}
+
 #[derive(StructOpt, Debug)]
 struct Opt {
     /// Run in debug mode
@@ -290,14 +648,266 @@ struct Opt {
     /// Set window height
     #[structopt(long = "height", default_value = "800")]
     height: u32,
+
+    /// Display type to start with (lines, boxes, circle, waterfall, bars, goniometer)
+    #[structopt(long = "display", default_value = "lines")]
+    display: DisplayType,
+
+    /// Number of frames kept in the waterfall view, from 1 to 10000
+    #[structopt(long = "history", default_value = "400", parse(try_from_str = parse_history))]
+    history: usize,
+
+    /// Color map of the waterfall view (spectrum, heat, grayscale)
+    #[structopt(long = "color-map", default_value = "spectrum")]
+    color_map: ColorMap,
+
+    /// Window applied before the FFT (hann, hamming, blackman-harris, flat-top, rectangular, kaiser)
+    #[structopt(long = "window", default_value = "hamming")]
+    window: WindowFunction,
+
+    /// Level the oscilloscope triggers on when the signal rises through it
+    #[structopt(long = "trigger-level", default_value = "0", parse(try_from_str = parse_finite))]
+    trigger_level: f32,
+
+    /// Sound file to analyze instead of picking a source
+    #[structopt(long = "file", parse(from_os_str))]
+    file: Option<PathBuf>,
+
+    /// Device channels to analyze as left and right, counting from 1
+    #[structopt(long = "channels", default_value = "1,2", parse(try_from_str = parse_channel_pair))]
+    channels: Sides<usize>,
+
+    /// Device to start with, by index or part of its name
+    #[structopt(long = "device")]
+    device: Option<String>,
+
+    /// Audio host backend to get devices from
+    #[structopt(long = "host", default_value = default_host_name(), parse(try_from_str = parse_host))]
+    host: HostId,
+
+    /// Print the available devices with their supported configs, then exit
+    #[structopt(long = "list-devices")]
+    list_devices: bool,
+
+    /// Samples per channel the FFT runs over, a power of two from 256 to 32768
+    #[structopt(long = "fft-size", default_value = "4096", parse(try_from_str = parse_fft_size))]
+    fft_size: usize,
+
+    /// Samples from one analysis frame to the next, from 64 to 262144, frames overlap if it's less than the FFT size
+    #[structopt(long = "hop-size", default_value = "512", parse(try_from_str = parse_hop_size))]
+    hop_size: usize,
+
+    /// How the spectrum is split into bands (log, octave, third-octave, sixth-octave, twelfth-octave, mel, bark)
+    #[structopt(long = "scale", default_value = "log")]
+    scale: BandScale,
+
+    /// Number of bands from 20 Hz to 20 kHz on the log and mel scales, from 1 to 4096
+    #[structopt(long = "bands", default_value = "256", parse(try_from_str = parse_bands))]
+    bands: usize,
+
+    /// What magnitudes are shown as (pretty, linear, db)
+    #[structopt(long = "magnitude", default_value = "pretty")]
+    magnitude: MagnitudeScale,
+
+    /// Level in dBFS at the bottom of the displays on the db magnitude scale
+    #[structopt(long = "min-db", default_value = "-90", allow_hyphen_values = true, parse(try_from_str = parse_finite))]
+    min_db: f32,
+
+    /// Level in dBFS at the top of the displays on the db magnitude scale
+    #[structopt(long = "max-db", default_value = "0", allow_hyphen_values = true, parse(try_from_str = parse_finite))]
+    max_db: f32,
+
+    /// Frequency weighting applied when normalizing (tilt, a, c, z, itu-r-468, pink)
+    #[structopt(long = "weighting", default_value = "tilt")]
+    weighting: Weighting,
+
+    /// Slope of the pink weighting in dB per octave, 3 makes pink noise flat
+    #[structopt(long = "pink-slope", default_value = "3", allow_hyphen_values = true, parse(try_from_str = parse_finite))]
+    pink_slope: f32,
+
+    /// Seconds between device rescans, 0 to only rescan on demand
+    #[structopt(long = "scan-interval", default_value = "2")]
+    scan_interval: u64,
+
+    /// Sample rate to ask devices for, in Hz, otherwise their preferred one
+    #[structopt(long = "sample-rate")]
+    sample_rate: Option<u32>,
+
+    /// Frequency of the left channel of generated test signals, in Hz, up to 22050
+    #[structopt(long = "left-frequency", default_value = "440", parse(try_from_str = parse_tone_frequency))]
+    left_frequency: f32,
+
+    /// Frequency of the right channel of generated test signals, in Hz, up to 22050
+    #[structopt(long = "right-frequency", default_value = "440", parse(try_from_str = parse_tone_frequency))]
+    right_frequency: f32,
+
+    /// Amplitude of the left channel of generated test signals, from 0 to 1
+    #[structopt(long = "left-amplitude", default_value = "0.5", parse(try_from_str = parse_amplitude))]
+    left_amplitude: f32,
+
+    /// Amplitude of the right channel of generated test signals, from 0 to 1
+    #[structopt(long = "right-amplitude", default_value = "0.5", parse(try_from_str = parse_amplitude))]
+    right_amplitude: f32,
+}
+
+fn parse_channel_pair(s: &str) -> Result<Sides<usize>, String> {
+    let parse_channel = |channel: &str| match channel.trim().parse::<usize>() {
+        Ok(channel) if channel > 0 => Ok(channel - 1),
+        _ => Err(format!("invalid channel: {}", channel)),
+    };
+
+    match s.split_once(',') {
+        Some((left, right)) => Ok(Sides {
+            left: parse_channel(left)?,
+            right: parse_channel(right)?,
+        }),
+        None => Err(format!("expected two channels like 1,2, got: {}", s)),
+    }
+}
+
+fn parse_history(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(count) if (1..=MAX_HISTORY).contains(&count) => Ok(count),
+        Ok(_) => Err(format!(
+            "the history has to be from 1 to {} frames",
+            MAX_HISTORY
+        )),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+fn parse_fft_size(s: &str) -> Result<usize, String> {
+    let size: usize = s.parse().map_err(|_| format!("{} isn't a number", s))?;
+
+    if size.is_power_of_two() && (MIN_CLIP_SIZE..=MAX_CLIP_SIZE).contains(&size) {
+        Ok(size)
+    } else {
+        Err(format!(
+            "{} isn't a power of two from {} to {}",
+            size, MIN_CLIP_SIZE, MAX_CLIP_SIZE
+        ))
+    }
+}
+
+fn parse_hop_size(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(size) if (MIN_HOP_SIZE..=MAX_HOP_SIZE).contains(&size) => Ok(size),
+        Ok(_) => Err(format!(
+            "the hop size has to be from {} to {}",
+            MIN_HOP_SIZE, MAX_HOP_SIZE
+        )),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+fn parse_bands(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(count) if (1..=MAX_BANDS).contains(&count) => Ok(count),
+        Ok(_) => Err(format!("there have to be from 1 to {} bands", MAX_BANDS)),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+// NaN and infinity would spread through every row they touch
+fn parse_finite(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("{} isn't a finite number", s)),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+// structopt parses options one at a time, so this one gets checked once they're all in
+fn check_db_range(min_db: f32, max_db: f32) -> Result<(), String> {
+    if min_db < max_db {
+        Ok(())
+    } else {
+        Err("--min-db has to be below --max-db".to_string())
+    }
+}
+
+// above half the generator's sample rate a tone would alias
+fn parse_tone_frequency(s: &str) -> Result<f32, String> {
+    let max_frequency = GENERATOR_SAMPLE_RATE as f32 / 2f32;
+
+    match s.parse::<f32>() {
+        Ok(frequency) if frequency > 0f32 && frequency <= max_frequency => Ok(frequency),
+        Ok(_) => Err(format!(
+            "the frequency has to be above 0 and at most {} Hz",
+            max_frequency
+        )),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+fn parse_amplitude(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(amplitude) if (0f32..=1f32).contains(&amplitude) => Ok(amplitude),
+        Ok(_) => Err("the amplitude has to be from 0 to 1".to_string()),
+        Err(_) => Err(format!("{} isn't a number", s)),
+    }
+}
+
+fn default_host_name() -> &'static str {
+    cpal::default_host().id().name()
+}
+
+fn parse_host(s: &str) -> Result<HostId, String> {
+    cpal::available_hosts()
+        .into_iter()
+        .find(|host_id| host_id.name().eq_ignore_ascii_case(s))
+        .ok_or_else(|| {
+            let available: Vec<&str> = cpal::available_hosts()
+                .iter()
+                .map(|host_id| host_id.name())
+                .collect();
+            format!("unknown host {}, available: {}", s, available.join(", "))
+        })
+}
+
+fn list_devices(host: HostId) -> Result<(), SoundProxyError> {
+    let mut sound_proxy = SoundProxy::default();
+    sound_proxy.set_host(host)?;
+    sound_proxy.scan_devices()?;
+
+    for (index, name, configs) in sound_proxy.get_device_configs() {
+        println!("{}: {}", index, name);
+        match configs {
+            Ok(configs) => {
+                for config in configs {
+                    println!(
+                        "    {} channels, {}-{} Hz, {:?}",
+                        config.channels(),
+                        config.min_sample_rate().0,
+                        config.max_sample_rate().0,
+                        config.sample_format()
+                    );
+                }
+            }
+            Err(error) => println!("    {}", error),
+        }
+    }
+
+    Ok(())
 }
 
 fn main() -> iced::Result {
     let opt = Opt::from_args();
+    if let Err(error) = check_db_range(opt.min_db, opt.max_db) {
+        clap::Error::with_description(&error, clap::ErrorKind::ValueValidation).exit();
+    }
     if opt.debug {
         println!("options: {:?}", opt);
     }
 
+    if opt.list_devices {
+        if let Err(error) = list_devices(opt.host) {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     App::run(Settings {
         window: window::Settings {
             size: (opt.width, opt.height),
@@ -314,3 +924,106 @@ fn main() -> iced::Result {
         ..Settings::with_flags(opt)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_pair_is_one_based() {
+        assert_eq!(parse_channel_pair("1,2"), Ok(Sides { left: 0, right: 1 }));
+        assert_eq!(
+            parse_channel_pair(" 4 , 3 "),
+            Ok(Sides { left: 3, right: 2 })
+        );
+    }
+
+    #[test]
+    fn channel_pair_rejects_bad_channels() {
+        assert!(parse_channel_pair("0,1").is_err());
+        assert!(parse_channel_pair("1").is_err());
+        assert!(parse_channel_pair("left,right").is_err());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        assert_eq!(parse_history("400"), Ok(400));
+        assert_eq!(parse_history("1"), Ok(1));
+        assert_eq!(parse_history("10000"), Ok(MAX_HISTORY));
+        assert!(parse_history("0").is_err());
+        assert!(parse_history("1000000000").is_err());
+        assert!(parse_history("long").is_err());
+    }
+
+    #[test]
+    fn fft_size_is_a_power_of_two_in_range() {
+        assert_eq!(parse_fft_size("4096"), Ok(4096));
+        assert_eq!(parse_fft_size("256"), Ok(MIN_CLIP_SIZE));
+        assert_eq!(parse_fft_size("32768"), Ok(MAX_CLIP_SIZE));
+        assert!(parse_fft_size("4000").is_err());
+        assert!(parse_fft_size("128").is_err());
+        assert!(parse_fft_size("65536").is_err());
+        assert!(parse_fft_size("big").is_err());
+    }
+
+    #[test]
+    fn hop_size_has_a_minimum() {
+        assert_eq!(parse_hop_size("1024"), Ok(1024));
+        assert_eq!(parse_hop_size("64"), Ok(MIN_HOP_SIZE));
+        assert_eq!(parse_hop_size("262144"), Ok(MAX_HOP_SIZE));
+        assert!(parse_hop_size("63").is_err());
+        assert!(parse_hop_size("262145").is_err());
+        assert!(parse_hop_size("18446744073709551615").is_err());
+        assert!(parse_hop_size("0").is_err());
+        assert!(parse_hop_size("-1").is_err());
+    }
+
+    #[test]
+    fn bands_need_at_least_one() {
+        assert_eq!(parse_bands("64"), Ok(64));
+        assert_eq!(parse_bands("1"), Ok(1));
+        assert_eq!(parse_bands("4096"), Ok(MAX_BANDS));
+        assert!(parse_bands("0").is_err());
+        assert!(parse_bands("4097").is_err());
+        assert!(parse_bands("100000000").is_err());
+        assert!(parse_bands("many").is_err());
+    }
+
+    #[test]
+    fn finite_rejects_nan_and_infinity() {
+        assert_eq!(parse_finite("-90"), Ok(-90f32));
+        assert_eq!(parse_finite("0.5"), Ok(0.5));
+        assert!(parse_finite("NaN").is_err());
+        assert!(parse_finite("inf").is_err());
+        assert!(parse_finite("-inf").is_err());
+        assert!(parse_finite("loud").is_err());
+    }
+
+    #[test]
+    fn db_range_has_min_below_max() {
+        assert!(check_db_range(-90f32, 0f32).is_ok());
+        assert!(check_db_range(0f32, 0f32).is_err());
+        assert!(check_db_range(-10f32, -20f32).is_err());
+    }
+
+    #[test]
+    fn tone_frequency_stays_below_nyquist() {
+        assert_eq!(parse_tone_frequency("440"), Ok(440f32));
+        assert_eq!(parse_tone_frequency("22050"), Ok(22050f32));
+        assert!(parse_tone_frequency("22051").is_err());
+        assert!(parse_tone_frequency("0").is_err());
+        assert!(parse_tone_frequency("-440").is_err());
+        assert!(parse_tone_frequency("NaN").is_err());
+        assert!(parse_tone_frequency("inf").is_err());
+    }
+
+    #[test]
+    fn amplitude_is_from_0_to_1() {
+        assert_eq!(parse_amplitude("0"), Ok(0f32));
+        assert_eq!(parse_amplitude("0.5"), Ok(0.5));
+        assert_eq!(parse_amplitude("1"), Ok(1f32));
+        assert!(parse_amplitude("1.5").is_err());
+        assert!(parse_amplitude("-0.1").is_err());
+        assert!(parse_amplitude("NaN").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use cpal::Sample;
+use rtrb::Producer;
+
+// custom de-interleaving iterator, normalizes any sample format to f32
+struct RawSoundData<'a, T: Sample> {
+    data: &'a [T],
+    num_channels: usize,
+    pos: usize,
+}
+
+impl<'a, T: Sample> Iterator for RawSoundData<'a, T> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            None
+        } else {
+            let val = self.data[self.pos].to_f32();
+            self.pos += self.num_channels;
+            Some(val)
+        }
+    }
+}
+
+// the left and right channels out of a buffer with any number of them, stereo interleaved
+pub fn stereo_frames<T: Sample>(
+    data: &[T],
+    num_channels: usize,
+    left: usize,
+    right: usize,
+) -> impl Iterator<Item = f32> + '_ {
+    let left = RawSoundData {
+        data,
+        num_channels,
+        pos: left,
+    };
+    let right = RawSoundData {
+        data,
+        num_channels,
+        pos: right,
+    };
+
+    left.zip(right).flat_map(|(left, right)| [left, right])
+}
+
+// runs on the audio thread, so it never waits: if the analyzer fell behind and the queue is full,
+// the newest samples get dropped
+pub fn push_frames<T: Sample>(
+    producer: &mut Producer<f32>,
+    data: &[T],
+    num_channels: usize,
+    left: usize,
+    right: usize,
+) {
+    // whole frames only, so left and right stay in step
+    let num_samples = (2 * (data.len() / num_channels)).min(producer.slots() & !1);
+    if let Ok(chunk) = producer.write_chunk_uninit(num_samples) {
+        chunk.fill_from_iter(stereo_frames(data, num_channels, left, right));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtrb::RingBuffer;
+
+    // 3 channels, the middle one is left out
+    const FRAMES: [f32; 9] = [0.1, 0.9, 0.2, 0.3, 0.9, 0.4, 0.5, 0.9, 0.6];
+
+    #[test]
+    fn stereo_frames_picks_the_pair() {
+        let frames: Vec<f32> = stereo_frames(&FRAMES, 3, 2, 0).collect();
+
+        assert_eq!(frames, [0.2, 0.1, 0.4, 0.3, 0.6, 0.5]);
+    }
+
+    #[test]
+    fn push_frames_writes_whole_frames_only() {
+        // room for 2 and a half frames
+        let (mut producer, mut consumer) = RingBuffer::new(5);
+
+        push_frames(&mut producer, &FRAMES, 3, 0, 2);
+
+        assert_eq!(consumer.slots(), 4);
+        let chunk = consumer.read_chunk(4).expect("read pushed frames");
+        assert_eq!(
+            chunk.into_iter().collect::<Vec<f32>>(),
+            [0.1, 0.2, 0.3, 0.4]
+        );
+    }
+
+    #[test]
+    fn push_frames_ignores_a_partial_frame_in_the_data() {
+        let (mut producer, consumer) = RingBuffer::new(16);
+
+        push_frames(&mut producer, &FRAMES[..8], 3, 0, 2);
+
+        assert_eq!(consumer.slots(), 4);
+    }
+
+    #[test]
+    fn push_frames_drops_the_newest_when_full() {
+        let (mut producer, mut consumer) = RingBuffer::new(8);
+
+        push_frames(&mut producer, &FRAMES, 3, 0, 2);
+        push_frames(&mut producer, &[0.7f32, 0.9, 0.8, 1.0, 0.9, 1.0], 3, 0, 2);
+        // full now, this one goes nowhere
+        push_frames(&mut producer, &FRAMES, 3, 0, 2);
+
+        let chunk = consumer.read_chunk(8).expect("read pushed frames");
+        assert_eq!(
+            chunk.into_iter().collect::<Vec<f32>>(),
+            [0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8]
+        );
+        assert_eq!(consumer.slots(), 0);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::VecDeque;
+use std::iter;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use rtrb::Consumer;
+use spectrum_analyzer::{samples_fft_to_spectrum, FrequencyLimit};
+
+use crate::fft_window::WindowFunction;
+use crate::frequency_bands::{Band, BandScale, MIN_FREQUENCY};
+use crate::sound_transformer::SoundTransformer;
+use crate::Sides;
+
+// how long the worker naps when there aren't enough new samples for a frame
+const IDLE_WAIT: Duration = Duration::from_millis(2);
+// frames waiting for the UI, past this the newest ones get dropped,
+// a few ticks' worth at the smallest hop size
+const FRAME_QUEUE_CAP: usize = 128;
+// how far the worker may fall behind the sound before it skips ahead to the newest samples
+const MAX_LAG: Duration = Duration::from_millis(100);
+// smaller hops mean thousands of FFTs a second, more than the worker can keep up with
+pub const MIN_HOP_SIZE: usize = 64;
+// a few seconds between frames, any longer and the display might as well be frozen
+pub const MAX_HOP_SIZE: usize = 262144;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisSettings {
+    // samples per channel the FFT runs over, a power of two
+    pub clip_size: usize,
+    // samples per channel from the end of one frame to the end of the next
+    pub hop_size: usize,
+    pub window: WindowFunction,
+    // how the spectrum gets gathered into bands from 20 Hz to 20 kHz,
+    // num_bands is for scales that can have any number of them
+    pub band_scale: BandScale,
+    pub num_bands: usize,
+    pub transformer: SoundTransformer,
+}
+
+// everything about the settings that doesn't change from frame to frame
+struct AnalysisPlan {
+    settings: AnalysisSettings,
+    window: Vec<f32>,
+    bands: Arc<[Band]>,
+    // the weighting curve at every FFT bin
+    bin_gains: Vec<f32>,
+    // what a band reads for a full scale sine
+    full_scale: f32,
+}
+
+impl AnalysisPlan {
+    fn new(settings: AnalysisSettings, sample_rate: u32) -> Self {
+        let window = settings.window.coefficients(settings.clip_size);
+        let weight_sum = window.iter().sum::<f32>();
+        let weight_square_sum = window.iter().map(|weight| weight * weight).sum::<f32>();
+
+        // a full scale sine on a bin peaks at half the FFT size, shrunk by the window's average weight
+        let peak = weight_sum / 2f32;
+        // the window spreads the sine over a few bins, summing their energy gives the peak's
+        // times the equivalent noise bandwidth, in bins
+        let noise_bandwidth =
+            settings.clip_size as f32 * weight_square_sum / (weight_sum * weight_sum);
+        let full_scale = if settings.band_scale.sums_energy() {
+            peak * noise_bandwidth.sqrt()
+        } else {
+            peak
+        };
+
+        // bins from 0 Hz up to and including half the sample rate, the ones below what's shown
+        // get the lowest shown frequency's gain so the curves don't blow up at 0 Hz
+        let bin_width = sample_rate as f32 / settings.clip_size as f32;
+        let bin_gains = (0..=settings.clip_size / 2)
+            .map(|bin| {
+                let frequency = (bin as f32 * bin_width).max(MIN_FREQUENCY);
+                settings.transformer.weighting_gain(frequency)
+            })
+            .collect();
+
+        Self {
+            settings,
+            window,
+            bands: settings.band_scale.bands(settings.num_bands).into(),
+            bin_gains,
+            full_scale,
+        }
+    }
+}
+
+pub struct AnalysisFrame {
+    // samples per channel since the stream started, up to the end of this frame
+    pub time: u64,
+    pub sample_rate: u32,
+    pub settings: AnalysisSettings,
+    // the bands the rows are for, shared by every frame with the same settings
+    pub bands: Arc<[Band]>,
+
+    // the samples the spectrum was computed from, oldest first,
+    // only on the newest frame of each batch since the displays only look at the latest ones
+    pub raw: Option<Sides<Vec<f32>>>,
+    // prettified magnitudes, one row per band, lowest first
+    pub rows: Sides<Vec<f32>>,
+}
+
+// cuts the stream into frames every hop_size samples, runs the FFT and the transformer on them,
+// all on its own thread, so what comes out only depends on the samples and not on how often the UI asks
+pub struct SoundAnalyzer {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+
+    settings: Sender<AnalysisSettings>,
+    frames: Receiver<AnalysisFrame>,
+}
+
+impl SoundAnalyzer {
+    // samples come in stereo interleaved
+    pub fn spawn(
+        mut samples: Consumer<f32>,
+        sample_rate: u32,
+        settings: AnalysisSettings,
+    ) -> SoundAnalyzer {
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = running.clone();
+
+        let (settings_sender, settings_receiver) = mpsc::channel();
+        let (frame_sender, frame_receiver) = mpsc::sync_channel(FRAME_QUEUE_CAP);
+
+        let thread = thread::spawn(move || {
+            let max_lag = (MAX_LAG.as_secs_f64() * sample_rate as f64) as u64;
+            let mut plan = AnalysisPlan::new(settings, sample_rate);
+            let mut pending = Sides::<VecDeque<f32>>::default();
+            // the previous frame's rows, for smoothing
+            let mut rows = Sides::<Vec<f32>>::default();
+            // samples per channel taken from the queue so far
+            let mut time = 0u64;
+            let mut next_frame_end = settings.clip_size as u64;
+
+            while running_clone.load(Ordering::Relaxed) {
+                // only the latest settings matter, no need to plan for the ones in between
+                if let Some(settings) = settings_receiver.try_iter().last() {
+                    plan = AnalysisPlan::new(settings, sample_rate);
+                }
+                let settings = plan.settings;
+
+                // whole frames only, so left and right stay in step
+                if let Ok(chunk) = samples.read_chunk(samples.slots() & !1) {
+                    let (first, second) = chunk.as_slices();
+                    let interleaved = first.iter().chain(second);
+
+                    pending.left.extend(interleaved.clone().step_by(2));
+                    pending.right.extend(interleaved.skip(1).step_by(2));
+                    time += (chunk.len() / 2) as u64;
+                    chunk.commit_all();
+                }
+
+                // after a switch to a bigger clip, the first frame has to wait until there's enough
+                let oldest = time - pending.left.len() as u64;
+                next_frame_end = next_frame_end.max(oldest + settings.clip_size as u64);
+
+                if next_frame_end > time {
+                    thread::sleep(IDLE_WAIT);
+                    continue;
+                }
+
+                // too slow to analyze every hop, the sound keeps going so catch up with it
+                if time - next_frame_end > max_lag {
+                    next_frame_end = time;
+                }
+
+                while next_frame_end <= time {
+                    // dropping the analyzer shouldn't have to wait for a backlog
+                    if !running_clone.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    let start = (next_frame_end - settings.clip_size as u64 - oldest) as usize;
+                    let newest = next_frame_end + settings.hop_size as u64 > time;
+                    let frame = analyze(
+                        &pending,
+                        start..start + settings.clip_size,
+                        &rows,
+                        next_frame_end,
+                        sample_rate,
+                        &plan,
+                        newest,
+                    );
+                    rows = frame.rows.clone();
+
+                    match frame_sender.try_send(frame) {
+                        Ok(()) => next_frame_end += settings.hop_size as u64,
+                        // the UI isn't taking frames, no use analyzing more until it does
+                        Err(TrySendError::Full(_)) => {
+                            next_frame_end = time + settings.hop_size as u64
+                        }
+                        Err(TrySendError::Disconnected(_)) => return,
+                    }
+                }
+
+                // only keep what the next frame still needs, at most a clip and a hop's worth
+                let needed_from = next_frame_end.saturating_sub(settings.clip_size as u64);
+                let stale = needed_from.saturating_sub(oldest) as usize;
+                pending.left.drain(..stale.min(pending.left.len()));
+                pending.right.drain(..stale.min(pending.right.len()));
+            }
+        });
+
+        Self {
+            running,
+            thread: Some(thread),
+
+            settings: settings_sender,
+            frames: frame_receiver,
+        }
+    }
+
+    // takes effect from the next frame on
+    pub fn set_settings(&self, settings: AnalysisSettings) {
+        // the thread only goes away with the analyzer, so there's always someone listening
+        let _ = self.settings.send(settings);
+    }
+
+    // everything analyzed since the last call, oldest first
+    pub fn take_frames(&self) -> Vec<AnalysisFrame> {
+        self.frames.try_iter().collect()
+    }
+}
+
+impl Drop for SoundAnalyzer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // a panicked worker has nothing left to clean up, no reason to take the UI down with it
+            let _ = thread.join();
+        }
+    }
+}
+
+fn analyze(
+    pending: &Sides<VecDeque<f32>>,
+    range: Range<usize>,
+    old_rows: &Sides<Vec<f32>>,
+    time: u64,
+    sample_rate: u32,
+    plan: &AnalysisPlan,
+    keep_raw: bool,
+) -> AnalysisFrame {
+    let settings = plan.settings;
+    // a float file or device can hand over NaN or infinity, which the FFT refuses and the displays can't draw
+    let finite = |sample: &f32| if sample.is_finite() { *sample } else { 0f32 };
+    let raw: Sides<Vec<f32>> = Sides {
+        left: pending.left.range(range.clone()).map(finite).collect(),
+        right: pending.right.range(range).map(finite).collect(),
+    };
+
+    let bin_width = sample_rate as f32 / settings.clip_size as f32;
+
+    // bins are as wide as the sample rate makes them, rows are whatever the bands say
+    let to_rows = |data: &[f32], old_rows: &[f32]| -> Vec<f32> {
+        let windowed: Vec<f32> = data
+            .iter()
+            .zip(plan.window.iter())
+            .map(|(sample, weight)| sample * weight)
+            .collect();
+        let magnitudes: Vec<f32> =
+            samples_fft_to_spectrum(&windowed, sample_rate, FrequencyLimit::All, None)
+                .expect("frequency spectrum conversion of finite samples")
+                .data()
+                .iter()
+                .zip(plan.bin_gains.iter())
+                .map(|((_, magnitude), gain)| magnitude.val() * gain)
+                .collect();
+
+        settings
+            .band_scale
+            .aggregate(&magnitudes, bin_width, &plan.bands)
+            .into_iter()
+            .zip(plan.bands.iter())
+            .zip(old_rows.iter().chain(iter::repeat(&0f32))) // lengthen the old rows if needed
+            .map(|((new, band), old)| {
+                // apply the prettifying transformation
+                settings
+                    .transformer
+                    .apply(*old, new, band.center, plan.full_scale)
+            })
+            .collect()
+    };
+
+    AnalysisFrame {
+        time,
+        sample_rate,
+        settings,
+        bands: plan.bands.clone(),
+
+        rows: Sides {
+            left: to_rows(&raw.left, &old_rows.left),
+            right: to_rows(&raw.right, &old_rows.right),
+        },
+        raw: keep_raw.then_some(raw),
+    }
+}
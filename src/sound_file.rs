@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+// decodes an audio file packet by packet, as stereo interleaved samples
+pub struct SoundFile {
+    path: PathBuf,
+
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+
+    pub sample_rate: u32,
+}
+
+impl SoundFile {
+    pub fn open(path: &Path) -> Result<SoundFile, Error> {
+        let file = File::open(path)?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+        // the extension helps pick the right format, but the contents are probed either way
+        let mut hint = Hint::new();
+        if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        let format = symphonia::default::get_probe()
+            .format(
+                &hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or(Error::Unsupported("no audio track"))?;
+
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or(Error::Unsupported("unknown sample rate"))?;
+        let track_id = track.id;
+
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+
+            format,
+            decoder,
+            track_id,
+
+            sample_rate,
+        })
+    }
+
+    // None once the end of the file is reached
+    pub fn next_chunk(&mut self) -> Option<Vec<f32>> {
+        loop {
+            let packet = self.format.next_packet().ok()?;
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(Error::DecodeError(_)) => continue, // corrupt packet, skip it
+                Err(_) => return None,
+            };
+
+            let num_channels = decoded.spec().channels.count();
+            let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            samples.copy_interleaved_ref(decoded);
+
+            return Some(to_stereo(samples.samples(), num_channels));
+        }
+    }
+
+    // start over from the beginning, reopening is the one thing every format supports
+    pub fn rewind(&mut self) -> Result<(), Error> {
+        *self = SoundFile::open(&self.path)?;
+        Ok(())
+    }
+}
+
+// mono gets duplicated, anything past the first two channels gets dropped
+fn to_stereo(samples: &[f32], num_channels: usize) -> Vec<f32> {
+    match num_channels {
+        0 => Vec::new(),
+        1 => samples
+            .iter()
+            .flat_map(|sample| [*sample, *sample])
+            .collect(),
+        2 => samples.to_vec(),
+        _ => samples
+            .chunks_exact(num_channels)
+            .flat_map(|frame| [frame[0], frame[1]])
+            .collect(),
+    }
+}
@@ -0,0 +1,215 @@
+use std::f32::consts::PI;
+use std::fmt;
+
+use crate::Sides;
+
+pub const GENERATOR_SAMPLE_RATE: u32 = 44100;
+
+// chirps sweep from the tone's frequency up to this, then start over
+const CHIRP_END: f32 = 20000f32;
+const CHIRP_SECONDS: f32 = 10f32;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Waveform {
+    Sine,
+    Chirp,
+    Square,
+    Saw,
+    WhiteNoise,
+    PinkNoise,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 6] = [
+        Waveform::Sine,
+        Waveform::Chirp,
+        Waveform::Square,
+        Waveform::Saw,
+        Waveform::WhiteNoise,
+        Waveform::PinkNoise,
+    ];
+}
+
+impl fmt::Display for Waveform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Waveform::Sine => "sine",
+            Waveform::Chirp => "chirp",
+            Waveform::Square => "square",
+            Waveform::Saw => "saw",
+            Waveform::WhiteNoise => "white noise",
+            Waveform::PinkNoise => "pink noise",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tone {
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+
+impl fmt::Display for Tone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Hz at {}", self.frequency, self.amplitude)
+    }
+}
+
+pub struct Generator {
+    waveform: Waveform,
+    channels: Sides<Oscillator>,
+}
+
+impl Generator {
+    pub fn new(waveform: Waveform, tones: &Sides<Tone>) -> Self {
+        Self {
+            waveform,
+            channels: Sides {
+                left: Oscillator::new(tones.left, 0x9e3779b9),
+                right: Oscillator::new(tones.right, 0x85ebca6b),
+            },
+        }
+    }
+
+    // stereo interleaved
+    pub fn next_chunk(&mut self, num_frames: usize) -> Vec<f32> {
+        let waveform = self.waveform;
+
+        (0..num_frames)
+            .flat_map(|_| {
+                [
+                    self.channels.left.next_sample(waveform),
+                    self.channels.right.next_sample(waveform),
+                ]
+            })
+            .collect()
+    }
+}
+
+struct Oscillator {
+    tone: Tone,
+
+    // fraction of a period, from 0 to 1
+    phase: f32,
+    // seconds into the current chirp
+    sweep_time: f32,
+
+    noise_state: u32,
+    pink_filter: [f32; 7],
+}
+
+impl Oscillator {
+    fn new(tone: Tone, seed: u32) -> Self {
+        Self {
+            tone,
+            phase: 0f32,
+            sweep_time: 0f32,
+            noise_state: seed,
+            pink_filter: [0f32; 7],
+        }
+    }
+
+    fn next_sample(&mut self, waveform: Waveform) -> f32 {
+        let sample = match waveform {
+            Waveform::Sine | Waveform::Chirp => (2f32 * PI * self.phase).sin(),
+            Waveform::Square => {
+                if self.phase < 0.5f32 {
+                    1f32
+                } else {
+                    -1f32
+                }
+            }
+            Waveform::Saw => 2f32 * self.phase - 1f32,
+            Waveform::WhiteNoise => self.white(),
+            Waveform::PinkNoise => self.pink(),
+        };
+
+        let frequency = if let Waveform::Chirp = waveform {
+            self.sweep_time += 1f32 / GENERATOR_SAMPLE_RATE as f32;
+            if self.sweep_time >= CHIRP_SECONDS {
+                self.sweep_time = 0f32;
+            }
+
+            // exponential sweep, so every octave takes the same time
+            let start = self.tone.frequency.min(CHIRP_END);
+            start * (CHIRP_END / start).powf(self.sweep_time / CHIRP_SECONDS)
+        } else {
+            self.tone.frequency
+        };
+
+        self.phase = (self.phase + frequency / GENERATOR_SAMPLE_RATE as f32).fract();
+
+        sample * self.tone.amplitude
+    }
+
+    // xorshift, uniform between -1 and 1
+    fn white(&mut self) -> f32 {
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+
+        self.noise_state as f32 / u32::MAX as f32 * 2f32 - 1f32
+    }
+
+    // Paul Kellet's refined filter, -3 dB per octave on top of white noise
+    fn pink(&mut self) -> f32 {
+        let white = self.white();
+        let b = &mut self.pink_filter;
+
+        b[0] = 0.99886 * b[0] + white * 0.0555179;
+        b[1] = 0.99332 * b[1] + white * 0.0750759;
+        b[2] = 0.96900 * b[2] + white * 0.153852;
+        b[3] = 0.86650 * b[3] + white * 0.3104856;
+        b[4] = 0.55000 * b[4] + white * 0.5329522;
+        b[5] = -0.7616 * b[5] - white * 0.016898;
+        let pink = b[0] + b[1] + b[2] + b[3] + b[4] + b[5] + b[6] + white * 0.5362;
+        b[6] = white * 0.115926;
+
+        // brings the peaks back to about the range of the other waveforms
+        pink * 0.11
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_finite(waveform: Waveform, frequency: f32) {
+        let tone = Tone {
+            frequency,
+            amplitude: 1f32,
+        };
+        let mut generator = Generator::new(
+            waveform,
+            &Sides {
+                left: tone,
+                right: tone,
+            },
+        );
+
+        // a little over one whole sweep, so the chirp wraps around once
+        let num_frames = ((CHIRP_SECONDS + 1f32) * GENERATOR_SAMPLE_RATE as f32) as usize;
+        let samples = generator.next_chunk(num_frames);
+
+        assert_eq!(samples.len(), 2 * num_frames);
+        assert!(samples
+            .iter()
+            .all(|sample| sample.is_finite() && sample.abs() <= 1f32));
+    }
+
+    #[test]
+    fn chirp_stays_finite() {
+        // barely above 0, a usual one, and the highest, which is above where the sweep ends
+        assert_finite(Waveform::Chirp, 0.01);
+        assert_finite(Waveform::Chirp, 440f32);
+        assert_finite(Waveform::Chirp, GENERATOR_SAMPLE_RATE as f32 / 2f32);
+    }
+
+    #[test]
+    fn every_waveform_stays_finite() {
+        for waveform in Waveform::ALL {
+            assert_finite(waveform, 1000f32);
+        }
+    }
+}
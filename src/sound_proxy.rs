@@ -1,188 +1,792 @@
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Device, Host, SampleRate, Stream, StreamConfig, SupportedStreamConfigRange};
+use cpal::{
+    BuildStreamError, Device, DevicesError, Host, HostId, HostUnavailable, PlayStreamError, Sample,
+    SampleFormat, SampleRate, Stream, StreamConfig, StreamError, SupportedStreamConfigRange,
+    SupportedStreamConfigsError,
+};
+
+use rtrb::{Producer, RingBuffer};
+
+use rustcertation::sample_queue::push_frames;
+
+use crate::fft_window::WindowFunction;
+use crate::frequency_bands::BandScale;
+use crate::sound_analyzer::{AnalysisFrame, AnalysisSettings, SoundAnalyzer};
+use crate::sound_file::SoundFile;
+use crate::sound_generator::{Generator, Tone, Waveform, GENERATOR_SAMPLE_RATE};
+use crate::sound_transformer::SoundTransformer;
+use crate::Sides;
+
+// samples per channel the FFT runs over, always a power of two
+pub const MIN_CLIP_SIZE: usize = 256;
+pub const MAX_CLIP_SIZE: usize = 32768;
+// stereo interleaved samples in flight between the audio thread and the analyzer
+const QUEUE_CAP: usize = 8 * 2 * 4096;
+
+// what every source other than a device hands over
+const STEREO_PAIR: Sides<usize> = Sides { left: 0, right: 1 };
+
+#[derive(Debug)]
+pub enum SoundProxyError {
+    HostUnavailable(HostId),
+    Devices(DevicesError),
+    SupportedConfigs(SupportedStreamConfigsError),
+    NoInputConfig,
+    // the device's name and how many channels it has
+    MissingChannels(String, usize),
+    BuildStream(BuildStreamError),
+    PlayStream(PlayStreamError),
+    Stream(StreamError),
+    File(symphonia::core::errors::Error),
+    Disconnected(String),
+}
 
-use ringbuffer::{ConstGenericRingBuffer, RingBufferExt};
+impl fmt::Display for SoundProxyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundProxyError::HostUnavailable(host_id) => {
+                write!(f, "the {} host isn't available", host_id.name())
+            }
+            SoundProxyError::Devices(error) => write!(f, "couldn't list devices: {}", error),
+            SoundProxyError::SupportedConfigs(error) => {
+                write!(f, "couldn't get the device's configs: {}", error)
+            }
+            SoundProxyError::NoInputConfig => write!(f, "the device has no input configs"),
+            SoundProxyError::MissingChannels(name, num_channels) => write!(
+                f,
+                "{} only has {} channel{}",
+                name,
+                num_channels,
+                if *num_channels == 1 { "" } else { "s" }
+            ),
+            SoundProxyError::BuildStream(error) => write!(f, "couldn't open the stream: {}", error),
+            SoundProxyError::PlayStream(error) => write!(f, "couldn't play the stream: {}", error),
+            SoundProxyError::Stream(error) => write!(f, "the stream stopped: {}", error),
+            SoundProxyError::File(error) => write!(f, "couldn't read the file: {}", error),
+            SoundProxyError::Disconnected(name) => write!(f, "{} was disconnected", name),
+        }
+    }
+}
 
-const CLIP_CAP: usize = 4096;
+impl std::error::Error for SoundProxyError {}
 
-#[derive(Clone)]
-pub struct Clip {
-    pub sample_rate: u32,
+impl From<DevicesError> for SoundProxyError {
+    fn from(error: DevicesError) -> Self {
+        SoundProxyError::Devices(error)
+    }
+}
 
-    pub left: ConstGenericRingBuffer<f32, CLIP_CAP>,
-    pub right: ConstGenericRingBuffer<f32, CLIP_CAP>,
+impl From<SupportedStreamConfigsError> for SoundProxyError {
+    fn from(error: SupportedStreamConfigsError) -> Self {
+        SoundProxyError::SupportedConfigs(error)
+    }
 }
 
-impl Default for Clip {
-    fn default() -> Clip {
-        let mut left = ConstGenericRingBuffer::<f32, CLIP_CAP>::new();
-        left.fill_default();
-        let mut right = ConstGenericRingBuffer::<f32, CLIP_CAP>::new();
-        right.fill_default();
+impl From<BuildStreamError> for SoundProxyError {
+    fn from(error: BuildStreamError) -> Self {
+        SoundProxyError::BuildStream(error)
+    }
+}
 
-        Self {
-            sample_rate: 0,
+impl From<PlayStreamError> for SoundProxyError {
+    fn from(error: PlayStreamError) -> Self {
+        SoundProxyError::PlayStream(error)
+    }
+}
 
-            left,
-            right,
-        }
+impl From<StreamError> for SoundProxyError {
+    fn from(error: StreamError) -> Self {
+        SoundProxyError::Stream(error)
     }
 }
 
-unsafe impl Send for Clip {}
-unsafe impl Sync for Clip {}
+impl From<symphonia::core::errors::Error> for SoundProxyError {
+    fn from(error: symphonia::core::errors::Error) -> Self {
+        SoundProxyError::File(error)
+    }
+}
 
-// custom de-interleaving iterator
-struct RawSoundData<'a> {
-    data: &'a [f32],
-    num_channels: usize,
-    pos: usize,
+pub enum Source {
+    Device(Device),
+    File(PathBuf),
+    Generator(Waveform, Sides<Tone>),
 }
 
-impl<'a> Iterator for RawSoundData<'a> {
-    type Item = f32;
+impl Source {
+    pub fn name(&self) -> String {
+        match self {
+            Source::Device(device) => device_name(device),
+            Source::File(path) => path.display().to_string(),
+            Source::Generator(waveform, tones) => {
+                format!("{} ({} / {})", waveform, tones.left, tones.right)
+            }
+        }
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.pos >= self.data.len() {
-            None
-        } else {
-            let val = self.data[self.pos];
-            self.pos += self.num_channels;
-            Some(val)
+// feeds the stream from a thread at real-time pace, for sources that don't come with their own callback
+struct PacedStream {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PacedStream {
+    // next_chunk gives stereo interleaved samples of any length, None ends the stream
+    fn spawn(
+        mut producer: Producer<f32>,
+        sample_rate: u32,
+        mut next_chunk: impl FnMut() -> Option<Vec<f32>> + Send + 'static,
+    ) -> PacedStream {
+        let running = Arc::new(AtomicBool::new(true));
+        let running_clone = running.clone();
+
+        let thread = thread::spawn(move || {
+            // hand over 10 ms at a time, about what a sound card would do
+            let block_frames = (sample_rate as usize / 100).max(1);
+            let block = block_frames * 2;
+
+            let start = Instant::now();
+            let mut frames_sent = 0u64;
+            let mut pending = Vec::new();
+
+            while running_clone.load(Ordering::Relaxed) {
+                while pending.len() < block {
+                    match next_chunk() {
+                        Some(chunk) => pending.extend(chunk),
+                        None => return,
+                    }
+                }
+                let rest = pending.split_off(block);
+
+                push_frames(
+                    &mut producer,
+                    &pending,
+                    2,
+                    STEREO_PAIR.left,
+                    STEREO_PAIR.right,
+                );
+                pending = rest;
+
+                // sleep until the wall clock catches up with the samples, so there's no drift
+                frames_sent += block_frames as u64;
+                let due = start + Duration::from_secs_f64(frames_sent as f64 / sample_rate as f64);
+                if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                    thread::sleep(wait);
+                }
+            }
+        });
+
+        Self {
+            running,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for PacedStream {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            // same as the analyzer, a panicked pacer shouldn't take the UI down with it
+            let _ = thread.join();
         }
     }
 }
 
 pub struct SoundProxy {
-    _sound_host: Host,
-    devices: Vec<Device>,
+    sound_host: Host,
+    sources: Vec<Source>,
 
-    clip: Arc<Mutex<Clip>>,
+    // takes whatever the audio thread queued up, lives as long as the stream
+    analyzer: Option<SoundAnalyzer>,
+    analysis_settings: AnalysisSettings,
     stream: Option<Stream>,
+    paced_stream: Option<PacedStream>,
+
+    selected: Option<usize>,
+    // device channels that end up as left and right
+    channel_pair: Sides<usize>,
+    // used if the device supports it, otherwise the device decides
+    requested_sample_rate: Option<u32>,
+
+    // errors from the audio thread, picked up on the UI side
+    stream_errors: (Sender<StreamError>, Receiver<StreamError>),
+
+    // a rescan running in the background, enumerating devices can take a while
+    device_scan: Option<Receiver<(HostId, DeviceScan)>>,
 }
 
+// every device the host has, with the most input channels it can be opened with
+type DeviceScan = Result<Vec<(Device, u16)>, SoundProxyError>;
+
+// starts out without devices, scan_devices fills them in
 impl Default for SoundProxy {
     fn default() -> SoundProxy {
-        let sound_host = cpal::default_host();
-        let devices = scan_devices(&sound_host);
-
         Self {
-            _sound_host: sound_host,
-            devices,
-
-            clip: Arc::new(Mutex::new(Clip::default())),
+            sound_host: cpal::default_host(),
+            sources: Vec::new(),
+
+            analyzer: None,
+            analysis_settings: AnalysisSettings {
+                clip_size: 4096,
+                hop_size: 512,
+                window: WindowFunction::Hamming,
+                band_scale: BandScale::Log,
+                num_bands: 256,
+                transformer: SoundTransformer::default(),
+            },
             stream: None,
+            paced_stream: None,
+
+            selected: None,
+            channel_pair: STEREO_PAIR,
+            requested_sample_rate: None,
+
+            stream_errors: mpsc::channel(),
+
+            device_scan: None,
         }
     }
 }
 
 // public
 impl SoundProxy {
-    pub fn scan_devices(&mut self) {
-        self.devices = scan_devices(&self._sound_host);
-    }
-
-    pub fn get_devices(&self) -> &Vec<Device> {
-        &self.devices
-    }
-
-    pub fn get_clip(&self) -> Clip {
-        self.clip
-            .clone()
-            .lock()
-            .expect("locked Clip in get_clip")
-            .clone()
-    }
-
-    pub fn select_device(&mut self, index: usize) {
-        let device = &self.devices[index];
-
-        let device_name = device.name().expect("device name in select_device");
-
-        let mut usable_configs: Vec<SupportedStreamConfigRange> = device
-            .supported_input_configs()
-            .expect("device's supported configs")
-            /* .map(|config| {
-                println!("{:#?}", config);
-                config
-            }) */
-            .filter(|config| config.channels() <= 2)
-            .collect();
-        usable_configs.sort_unstable_by_key(|config| -(config.channels() as i16));
-
-        let config: StreamConfig = usable_configs
-            .into_iter()
-            .next()
-            .expect("config to use in select_device")
-            //.with_max_sample_rate()
-            .with_sample_rate(SampleRate(44100))
-            .into();
-
-        println!("[{}]'s config: {:#?}", device_name, config);
-
-        let clip_clone = self.clip.clone();
-        let mut locked_clip = self
-            .clip
-            .lock()
-            .expect("locked Clip mutex in select_device");
-
-        locked_clip.sample_rate = config.sample_rate.0;
-
-        let stream = device
-            .build_input_stream(
-                &config,
-                move |data, _| {
-                    on_data(
-                        &mut clip_clone
-                            .lock()
-                            .expect("locked Clip mutex in data_callback"),
-                        data,
-                    )
+    // drops everything from the old host, devices need to be scanned again
+    pub fn set_host(&mut self, host_id: HostId) -> Result<(), SoundProxyError> {
+        let sound_host = cpal::host_from_id(host_id)
+            .map_err(|HostUnavailable| SoundProxyError::HostUnavailable(host_id))?;
+
+        self.unselect_source();
+        self.sources
+            .retain(|source| !matches!(source, Source::Device(_)));
+        self.sound_host = sound_host;
+
+        Ok(())
+    }
+
+    pub fn get_host_id(&self) -> HostId {
+        self.sound_host.id()
+    }
+
+    // the hosts compiled in and usable on this system
+    pub fn get_hosts(&self) -> Vec<HostId> {
+        cpal::available_hosts()
+    }
+
+    // keeps the selected source selected, unless it's a device that went away
+    pub fn scan_devices(&mut self) -> Result<(), SoundProxyError> {
+        let devices = scan_devices(&self.sound_host)?;
+        self.replace_devices(devices)
+    }
+
+    // picks up the last background scan if it's done, then starts the next one,
+    // so the UI thread never waits for the devices to answer
+    pub fn poll_devices(&mut self) -> Result<(), SoundProxyError> {
+        if let Some(device_scan) = &self.device_scan {
+            match device_scan.try_recv() {
+                Ok((host_id, devices)) => {
+                    self.device_scan = None;
+                    // a scan of the host before a switch is no use
+                    if host_id == self.get_host_id() {
+                        self.replace_devices(devices?)?;
+                    }
+                }
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => self.device_scan = None,
+            }
+        }
+
+        let host_id = self.get_host_id();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let devices = cpal::host_from_id(host_id)
+                .map_err(|HostUnavailable| SoundProxyError::HostUnavailable(host_id))
+                .and_then(|sound_host| scan_devices(&sound_host));
+            // nobody to tell if the proxy is already gone
+            let _ = sender.send((host_id, devices));
+        });
+        self.device_scan = Some(receiver);
+
+        Ok(())
+    }
+
+    // input devices only, plus the active one even if it's too busy to report its configs
+    fn replace_devices(&mut self, devices: Vec<(Device, u16)>) -> Result<(), SoundProxyError> {
+        let selected_name = self.selected.map(|index| self.sources[index].name());
+        let devices = devices.into_iter().filter_map(|(device, max_channels)| {
+            (max_channels > 0 || selected_name.as_deref() == Some(device_name(&device).as_str()))
+                .then_some(device)
+        });
+
+        // devices go first, other sources stay as they were
+        let others = self
+            .sources
+            .drain(..)
+            .filter(|source| !matches!(source, Source::Device(_)));
+
+        self.sources = devices.map(Source::Device).chain(others).collect();
+
+        if let Some(name) = selected_name {
+            match self.sources.iter().position(|source| source.name() == name) {
+                Some(index) => self.selected = Some(index),
+                None => {
+                    self.unselect_source();
+                    return Err(SoundProxyError::Disconnected(name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // one source per waveform, all with the same tones
+    pub fn add_generators(&mut self, tones: Sides<Tone>) {
+        self.sources.extend(
+            Waveform::ALL
+                .into_iter()
+                .map(|waveform| Source::Generator(waveform, tones.clone())),
+        );
+    }
+
+    pub fn get_sources(&self) -> &Vec<Source> {
+        &self.sources
+    }
+
+    // indices change with every rescan, names don't
+    pub fn find_source(&self, name: &str) -> Option<usize> {
+        self.sources.iter().position(|source| source.name() == name)
+    }
+
+    // an index into the sources, or else part of a device's name, ignoring case
+    pub fn find_device(&self, query: &str) -> Option<usize> {
+        let is_device = |index: &usize| matches!(self.sources.get(*index), Some(Source::Device(_)));
+
+        query.parse::<usize>().ok().filter(is_device).or_else(|| {
+            let query = query.to_lowercase();
+            (0..self.sources.len())
+                .filter(is_device)
+                .find(|index| self.sources[*index].name().to_lowercase().contains(&query))
+        })
+    }
+
+    // every device with everything it can be opened with
+    pub fn get_device_configs(
+        &self,
+    ) -> Vec<(
+        usize,
+        String,
+        Result<Vec<SupportedStreamConfigRange>, SoundProxyError>,
+    )> {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter_map(|(index, source)| match source {
+                Source::Device(device) => Some((
+                    index,
+                    device_name(device),
+                    device
+                        .supported_input_configs()
+                        .map(|configs| configs.collect())
+                        .map_err(SoundProxyError::from),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // checks that the file can be decoded, returns the index of its source
+    pub fn add_file(&mut self, path: PathBuf) -> Result<usize, SoundProxyError> {
+        SoundFile::open(&path)?;
+
+        let existing = self
+            .sources
+            .iter()
+            .position(|source| matches!(source, Source::File(file_path) if *file_path == path));
+
+        Ok(existing.unwrap_or_else(|| {
+            self.sources.push(Source::File(path));
+            self.sources.len() - 1
+        }))
+    }
+
+    // everything the analyzer came up with since the last call, oldest first
+    pub fn take_frames(&self) -> Vec<AnalysisFrame> {
+        self.analyzer
+            .as_ref()
+            .map(|analyzer| analyzer.take_frames())
+            .unwrap_or_default()
+    }
+
+    // the stream keeps going, the analyzer picks the new settings up from its next frame
+    pub fn set_analysis_settings(&mut self, settings: AnalysisSettings) {
+        self.analysis_settings = settings;
+        if let Some(analyzer) = &self.analyzer {
+            analyzer.set_settings(settings);
+        }
+    }
+
+    // doubles or halves the clip size, as long as it stays within bounds
+    pub fn shift_clip_size(&mut self, step: i32) {
+        let clip_size = self.analysis_settings.clip_size;
+        let clip_size = if step >= 0 {
+            clip_size << step
+        } else {
+            clip_size >> -step
+        };
+
+        if (MIN_CLIP_SIZE..=MAX_CLIP_SIZE).contains(&clip_size) {
+            self.set_analysis_settings(AnalysisSettings {
+                clip_size,
+                ..self.analysis_settings
+            });
+            println!("analyzing {} samples at a time", clip_size);
+        }
+    }
+
+    // changes the transformer the analyzer prettifies the spectrum with
+    pub fn update_transformer(&mut self, update: impl FnOnce(&mut SoundTransformer)) {
+        let mut transformer = self.analysis_settings.transformer;
+        update(&mut transformer);
+        self.set_analysis_settings(AnalysisSettings {
+            transformer,
+            ..self.analysis_settings
+        });
+    }
+
+    pub fn cycle_band_scale(&mut self) {
+        let band_scale = self.analysis_settings.band_scale.next();
+        self.set_analysis_settings(AnalysisSettings {
+            band_scale,
+            ..self.analysis_settings
+        });
+        println!("using {} bands", band_scale);
+    }
+
+    pub fn cycle_window(&mut self) {
+        let window = self.analysis_settings.window.next();
+        self.set_analysis_settings(AnalysisSettings {
+            window,
+            ..self.analysis_settings
+        });
+        println!("using {} window", window);
+    }
+
+    pub fn cycle_weighting(&mut self) {
+        let weighting = self.analysis_settings.transformer.weighting().next();
+        self.update_transformer(|transformer| transformer.set_weighting(weighting));
+        println!("using {} weighting", weighting);
+    }
+
+    pub fn cycle_magnitude_scale(&mut self) {
+        let magnitude_scale = self.analysis_settings.transformer.magnitude_scale().next();
+        self.update_transformer(|transformer| transformer.set_magnitude_scale(magnitude_scale));
+        println!("showing {} magnitudes", magnitude_scale);
+    }
+
+    pub fn set_channel_pair(&mut self, channel_pair: Sides<usize>) {
+        self.channel_pair = channel_pair;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.requested_sample_rate = sample_rate;
+    }
+
+    // the first error the current stream ran into, if any
+    pub fn take_stream_error(&self) -> Option<SoundProxyError> {
+        self.stream_errors
+            .1
+            .try_recv()
+            .ok()
+            .map(SoundProxyError::from)
+    }
+
+    // moves to the next or previous pair of channels, restarting the device stream
+    pub fn shift_channel_pair(&mut self, step: i32) -> Result<(), SoundProxyError> {
+        if let Some((index, Source::Device(_))) =
+            self.selected.map(|index| (index, &self.sources[index]))
+        {
+            let shift = |channel: usize| channel as i32 + 2 * step;
+            let (left, right) = (
+                shift(self.channel_pair.left),
+                shift(self.channel_pair.right),
+            );
+
+            if left.min(right) < 0 {
+                return Ok(());
+            }
+
+            // a device with an open stream may be too busy to say how many channels it has,
+            // so only opening it again can tell if the new pair fits
+            let old_pair = std::mem::replace(
+                &mut self.channel_pair,
+                Sides {
+                    left: left as usize,
+                    right: right as usize,
                 },
-                |error| eprintln!("{}", error),
-            )
-            .expect("stream in select_device");
+            );
+
+            // same as picking the device again, so the old stream's errors go with it
+            return match self.select_source(index) {
+                Err(SoundProxyError::MissingChannels(..)) => {
+                    self.channel_pair = old_pair;
+                    self.select_source(index)
+                }
+                result => {
+                    if result.is_ok() {
+                        println!("analyzing channels {} and {}", left + 1, right + 1);
+                    }
+                    result
+                }
+            };
+        }
 
-        // have to play the stream
-        stream.play().expect("playing stream in select_device");
-        self.stream = Some(stream);
+        Ok(())
     }
 
-    pub fn unselect_device(&mut self) {
+    pub fn select_source(&mut self, index: usize) -> Result<(), SoundProxyError> {
+        self.unselect_source();
+        self.selected = Some(index);
+
+        // a fresh queue for every stream, the producer goes wherever the samples come from
+        let (producer, consumer) = RingBuffer::new(QUEUE_CAP);
+
+        let sample_rate = match &self.sources[index] {
+            Source::Device(device) => {
+                let (stream, sample_rate) = open_device_stream(
+                    device,
+                    &self.channel_pair,
+                    self.requested_sample_rate,
+                    producer,
+                    self.stream_errors.0.clone(),
+                )?;
+                self.stream = Some(stream);
+                sample_rate
+            }
+            Source::File(path) => {
+                let (paced_stream, sample_rate) = open_file_stream(path, producer)?;
+                self.paced_stream = Some(paced_stream);
+                sample_rate
+            }
+            Source::Generator(waveform, tones) => {
+                self.paced_stream = Some(open_generator_stream(*waveform, tones, producer));
+                GENERATOR_SAMPLE_RATE
+            }
+        };
+        self.analyzer = Some(SoundAnalyzer::spawn(
+            consumer,
+            sample_rate,
+            self.analysis_settings,
+        ));
+
+        Ok(())
+    }
+
+    pub fn unselect_source(&mut self) {
+        self.selected = None;
         self.stream = None;
+        self.paced_stream = None;
+        self.analyzer = None;
+
+        // whatever the old stream complained about doesn't matter anymore
+        while self.stream_errors.1.try_recv().is_ok() {}
     }
 }
 
-fn on_data(clip: &mut Clip, data: &[f32]) {
-    clip.left.extend(RawSoundData {
-        data,
-        num_channels: 2,
-        pos: 0,
+fn open_device_stream(
+    device: &Device,
+    channel_pair: &Sides<usize>,
+    requested_sample_rate: Option<u32>,
+    producer: Producer<f32>,
+    errors: Sender<StreamError>,
+) -> Result<(Stream, u32), SoundProxyError> {
+    let device_name = device_name(device);
+
+    let required_channels = (channel_pair.left.max(channel_pair.right) + 1) as u16;
+
+    let mut usable_configs: Vec<SupportedStreamConfigRange> = device
+        .supported_input_configs()?
+        /* .map(|config| {
+            println!("{:#?}", config);
+            config
+        }) */
+        .collect();
+    // fewest channels that still cover the pair, otherwise as many as there are,
+    // then ones that can do the requested sample rate, then whatever needs the least converting
+    usable_configs.sort_unstable_by_key(|config| {
+        let channels = config.channels() as i32;
+        (
+            if channels >= required_channels as i32 {
+                channels
+            } else {
+                i32::MAX - channels
+            },
+            !requested_sample_rate.is_some_and(|rate| supports_rate(config, SampleRate(rate))),
+            sample_format_rank(config.sample_format()),
+        )
     });
-    clip.right.extend(RawSoundData {
-        data,
-        num_channels: 2,
-        pos: 1,
+
+    let usable_config = usable_configs
+        .into_iter()
+        .next()
+        .ok_or(SoundProxyError::NoInputConfig)?;
+
+    // the requested rate, or the device's preferred one, or the highest it can do
+    let preferred_sample_rate = device
+        .default_input_config()
+        .ok()
+        .map(|config| config.sample_rate());
+    let sample_rate = requested_sample_rate
+        .map(SampleRate)
+        .filter(|rate| supports_rate(&usable_config, *rate))
+        .or_else(|| preferred_sample_rate.filter(|rate| supports_rate(&usable_config, *rate)))
+        .unwrap_or_else(|| usable_config.max_sample_rate());
+
+    if let Some(rate) = requested_sample_rate {
+        if rate != sample_rate.0 {
+            println!(
+                "[{}] doesn't support {} Hz, using {} Hz",
+                device_name, rate, sample_rate.0
+            );
+        }
+    }
+
+    let supported_config = usable_config.with_sample_rate(sample_rate);
+    let sample_format = supported_config.sample_format();
+    let config: StreamConfig = supported_config.into();
+
+    // mono devices get their one channel on both sides when nothing else was asked for,
+    // any other pair the device doesn't have is an error
+    let num_channels = config.channels as usize;
+    let channel_pair = if channel_pair.left.max(channel_pair.right) < num_channels {
+        channel_pair.clone()
+    } else if num_channels == 1 && *channel_pair == STEREO_PAIR {
+        Sides { left: 0, right: 0 }
+    } else {
+        return Err(SoundProxyError::MissingChannels(device_name, num_channels));
+    };
+
+    println!(
+        "[{}]'s config: {:#?}, {:?} samples",
+        device_name, config, sample_format
+    );
+
+    let stream = match sample_format {
+        SampleFormat::F32 => {
+            build_input_stream::<f32>(device, &config, channel_pair, producer, errors)
+        }
+        SampleFormat::I16 => {
+            build_input_stream::<i16>(device, &config, channel_pair, producer, errors)
+        }
+        SampleFormat::U16 => {
+            build_input_stream::<u16>(device, &config, channel_pair, producer, errors)
+        }
+    }?;
+
+    // have to play the stream
+    stream.play()?;
+    Ok((stream, config.sample_rate.0))
+}
+
+fn build_input_stream<T: Sample>(
+    device: &Device,
+    config: &StreamConfig,
+    channel_pair: Sides<usize>,
+    mut producer: Producer<f32>,
+    errors: Sender<StreamError>,
+) -> Result<Stream, BuildStreamError> {
+    let num_channels = config.channels as usize;
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            push_frames(
+                &mut producer,
+                data,
+                num_channels,
+                channel_pair.left,
+                channel_pair.right,
+            )
+        },
+        move |error| {
+            // nobody to tell if the proxy is already gone
+            let _ = errors.send(error);
+        },
+    )
+}
+
+// lower is better
+fn sample_format_rank(sample_format: SampleFormat) -> u8 {
+    match sample_format {
+        SampleFormat::F32 => 0,
+        SampleFormat::I16 => 1,
+        SampleFormat::U16 => 2,
+    }
+}
+
+fn open_file_stream(
+    path: &Path,
+    producer: Producer<f32>,
+) -> Result<(PacedStream, u32), SoundProxyError> {
+    let mut file = SoundFile::open(path)?;
+    let sample_rate = file.sample_rate;
+
+    // loop the file forever
+    let paced_stream = PacedStream::spawn(producer, sample_rate, move || {
+        file.next_chunk().or_else(|| {
+            file.rewind().ok()?;
+            file.next_chunk()
+        })
     });
+
+    Ok((paced_stream, sample_rate))
 }
 
-// function instead of method so that it can be reused in the constructor
-fn scan_devices(sound_host: &Host) -> Vec<Device> {
-    sound_host
-        .devices()
-        .expect("iterator of available devices")
-        //.into_iter()
-        .filter(|device| {
-            let possibly_supported_configs = device.supported_input_configs();
+fn open_generator_stream(
+    waveform: Waveform,
+    tones: &Sides<Tone>,
+    producer: Producer<f32>,
+) -> PacedStream {
+    let mut generator = Generator::new(waveform, tones);
 
-            if let Ok(mut supported_configs) = possibly_supported_configs {
-                supported_configs.any(|config| config.channels() <= 2)
-            } else {
-                false
-            }
-        }) // keep only input devices
-        .collect()
-    //vec![sound_host.default_input_device().expect("default input device")]
+    PacedStream::spawn(producer, GENERATOR_SAMPLE_RATE, move || {
+        Some(generator.next_chunk(GENERATOR_SAMPLE_RATE as usize / 100))
+    })
+}
+
+fn supports_rate(config: &SupportedStreamConfigRange, sample_rate: SampleRate) -> bool {
+    config.min_sample_rate() <= sample_rate && sample_rate <= config.max_sample_rate()
+}
+
+fn max_input_channels(device: &Device) -> u16 {
+    device
+        .supported_input_configs()
+        .map(|configs| configs.map(|config| config.channels()).max().unwrap_or(0))
+        .unwrap_or(0)
+}
+
+// a device in use might not report its configs anymore, so filtering is left to the caller,
+// which knows which one is active
+fn scan_devices(sound_host: &Host) -> DeviceScan {
+    Ok(sound_host
+        .devices()?
+        .map(|device| {
+            let max_channels = max_input_channels(&device);
+            (device, max_channels)
+        })
+        .collect())
+}
+
+// names are only for showing, so a device that won't say its name still gets one
+fn device_name(device: &Device) -> String {
+    device
+        .name()
+        .unwrap_or_else(|_| String::from("unnamed device"))
 }
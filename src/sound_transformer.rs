@@ -1,7 +1,68 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::frequency_weighting::{Weighting, REFERENCE_FREQUENCY};
+
+// smallest amplitude that still gets a finite level, about -300 dB
+const MIN_AMPLITUDE: f32 = 1e-15f32;
+
+// what FFT magnitudes get turned into before they're drawn
+#[derive(Debug, Clone, Copy)]
+pub enum MagnitudeScale {
+    // the original hand tuned curve, in pixels
+    Pretty,
+    // amplitude relative to a full scale sine, from 0 to 1
+    Linear,
+    // level in dBFS, from 0 at the floor to 1 at the ceiling of the dB range
+    Decibel,
+}
+
+impl MagnitudeScale {
+    pub fn next(self) -> Self {
+        match self {
+            MagnitudeScale::Pretty => MagnitudeScale::Linear,
+            MagnitudeScale::Linear => MagnitudeScale::Decibel,
+            MagnitudeScale::Decibel => MagnitudeScale::Pretty,
+        }
+    }
+}
+
+impl fmt::Display for MagnitudeScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MagnitudeScale::Pretty => "pretty",
+            MagnitudeScale::Linear => "linear",
+            MagnitudeScale::Decibel => "db",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for MagnitudeScale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pretty" => Ok(MagnitudeScale::Pretty),
+            "linear" => Ok(MagnitudeScale::Linear),
+            "db" | "dbfs" => Ok(MagnitudeScale::Decibel),
+            _ => Err(format!("unknown magnitude scale: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct SoundTransformer {
+    magnitude_scale: MagnitudeScale,
+    min_db: f32,
+    max_db: f32,
+
     norm: bool,
     full_norm: bool,
     norm_scale: f32,
+    weighting: Weighting,
+    // dB per octave for pink compensation
+    pink_slope: f32,
 
     smooth: bool,
     flash_flood: bool,
@@ -16,9 +77,15 @@ impl Default for SoundTransformer {
         let moving_avg_k = get_moving_avg_coefficient(moving_avg_range);
 
         Self {
+            magnitude_scale: MagnitudeScale::Pretty,
+            min_db: -90f32,
+            max_db: 0f32,
+
             norm: true,
             full_norm: false,
             norm_scale: 1f32,
+            weighting: Weighting::Tilt,
+            pink_slope: 3f32,
 
             smooth: true,
             flash_flood: true,
@@ -30,29 +97,89 @@ impl Default for SoundTransformer {
 }
 
 impl SoundTransformer {
-    pub fn apply(&self, old: f32, new: f32, index: f32) -> f32 {
+    // full_scale is what a band reads for a full scale sine,
+    // it depends on the FFT size, the window and how the band was gathered
+    // new should already be weighted, see weighting_gain
+    pub fn apply(&self, old: f32, new: f32, frequency: f32, full_scale: f32) -> f32 {
+        let scaled = match self.magnitude_scale {
+            MagnitudeScale::Pretty => self.prettify(new, frequency),
+            // the scale is a plain gain on these, so the displays can be zoomed in on quiet sounds
+            MagnitudeScale::Linear => new / full_scale * self.norm_scale,
+            MagnitudeScale::Decibel => {
+                let level = 20f32
+                    * (new / full_scale * self.norm_scale)
+                        .max(MIN_AMPLITUDE)
+                        .log10();
+                ((level - self.min_db) / (self.max_db - self.min_db)).clamp(0f32, 1f32)
+            }
+        };
+
+        self.smoothen(old, scaled)
+    }
+
+    pub fn magnitude_scale(&self) -> MagnitudeScale {
+        self.magnitude_scale
+    }
+
+    pub fn db_range(&self) -> (f32, f32) {
+        (self.min_db, self.max_db)
+    }
+
+    pub fn weighting(&self) -> Weighting {
+        self.weighting
+    }
+
+    // the weighting that gets applied, None when normalization is off
+    pub fn active_weighting(&self) -> Option<Weighting> {
+        if self.norm {
+            Some(self.weighting)
+        } else {
+            None
+        }
+    }
+
+    // the gain the scale keys put on linear and dB magnitudes
+    pub fn gain_db(&self) -> f32 {
+        20f32 * self.norm_scale.log10()
+    }
+
+    pub fn pink_slope(&self) -> f32 {
+        self.pink_slope
+    }
+
+    // amplitude factor of the weighting curve, for every FFT bin before it's gathered into a band,
+    // since the curves change a lot across wide bands. the tilt is left to normalize
+    pub fn weighting_gain(&self, frequency: f32) -> f32 {
+        match self.active_weighting() {
+            Some(weighting) => 10f32.powf(weighting.gain_db(frequency, self.pink_slope) / 20f32),
+            None => 1f32,
+        }
+    }
+
+    fn prettify(&self, val: f32, frequency: f32) -> f32 {
         let base_scale = 0.25f32;
         let power = 1.5f32;
 
-        self.smoothen(
-            old,
-            self.normalize((base_scale * new).abs().powf(power), index),
-        )
+        self.normalize((base_scale * val).abs().powf(power), frequency)
     }
 
-    fn normalize(&self, val: f32, index: f32) -> f32 {
+    fn normalize(&self, val: f32, frequency: f32) -> f32 {
         let power = 0.7f32;
         let scale = 0.000000000015f32;
         let full_scale = 0.02f32;
 
-        if self.norm {
-            if self.full_norm {
-                val * (index + 1f32) * full_scale
-            } else {
-                val * (index + 1f32).powf(power) * scale * self.norm_scale
+        match self.active_weighting() {
+            Some(Weighting::Tilt) if self.full_norm => val * (frequency + 1f32) * full_scale,
+            Some(weighting) => {
+                // the standard curves were applied to the bins already, they take the tilt's place
+                // at the level it has where they're all 0 dB, so switching keeps the picture's size
+                let tilt_frequency = match weighting {
+                    Weighting::Tilt => frequency,
+                    _ => REFERENCE_FREQUENCY,
+                };
+                val * (tilt_frequency + 1f32).powf(power) * scale * self.norm_scale
             }
-        } else {
-            val
+            None => val,
         }
     }
 
@@ -70,6 +197,24 @@ impl SoundTransformer {
 
     // mutators ------------------------------------------------------------------------------------
 
+    pub fn set_weighting(&mut self, weighting: Weighting) {
+        self.weighting = weighting;
+    }
+
+    pub fn set_pink_slope(&mut self, pink_slope: f32) {
+        self.pink_slope = pink_slope;
+    }
+
+    pub fn set_magnitude_scale(&mut self, magnitude_scale: MagnitudeScale) {
+        self.magnitude_scale = magnitude_scale;
+    }
+
+    // the levels that end up at the bottom and the top of the displays, min has to be below max
+    pub fn set_db_range(&mut self, min_db: f32, max_db: f32) {
+        self.min_db = min_db;
+        self.max_db = max_db;
+    }
+
     pub fn toggle_norm(&mut self) {
         self.norm = !self.norm;
     }
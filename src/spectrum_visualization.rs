@@ -1,34 +1,122 @@
+use std::collections::VecDeque;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::fmt;
 use std::iter;
-use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
 
+use iced::alignment::Horizontal;
 use iced::widget::canvas::{
     gradient::Linear, path, stroke::Style, Canvas, Cursor, Frame, Geometry, LineCap, LineDash,
-    LineJoin, Program, Stroke,
+    LineJoin, Program, Stroke, Text,
 };
 use iced::widget::Container;
-use iced::{Color, Element, Length, Rectangle, Theme};
+use iced::{Color, Element, Length, Rectangle, Size, Theme};
 use iced_graphics::gradient::ColorStop;
 use iced_graphics::{Gradient, Point};
 
 use palette::RgbHue;
 use palette::{convert::IntoColor, Hsv, Hue, Srgb};
-use ringbuffer::RingBufferExt;
-use spectrum_analyzer::{samples_fft_to_spectrum, windows, FrequencyLimit};
 
-use crate::sound_proxy::Clip;
-use crate::sound_transformer::SoundTransformer;
-use crate::{AppMessage, ContentType, Sides};
+use crate::frequency_bands::{slot_bands, Band, BandScale};
+use crate::frequency_weighting::Weighting;
+use crate::sound_analyzer::{AnalysisFrame, AnalysisSettings};
+use crate::sound_transformer::MagnitudeScale;
+use crate::{AppMessage, ContentType, DisplayType, Opt, Sides};
+
+const BOX_SIZE: f32 = 8f32;
+const BOX_GAP: f32 = 2f32;
+
+const SCOPE_GAIN_STEP: f32 = 1.15f32;
+
+const GONIO_SAMPLES: usize = 1024;
+const GONIO_PERSISTENCE: usize = 8;
+const CORRELATION_METER_HEIGHT: f32 = 40f32;
+
+const BAR_GAP: f32 = 2f32;
+const BAR_LABEL_HEIGHT: f32 = 20f32;
+// room one band name needs, about "1.25k" at 12 px
+const BAR_LABEL_WIDTH: f32 = 36f32;
+
+const WATERFALL_CELL: f32 = 4f32;
+// rows of past spectra the waterfall keeps, far more than a screen shows
+pub const MAX_HISTORY: usize = 10000;
+const COLOR_LEVELS: u8 = 32;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ColorMap {
+    Spectrum,
+    Heat,
+    Grayscale,
+}
+
+impl ColorMap {
+    fn next(self) -> Self {
+        match self {
+            ColorMap::Spectrum => ColorMap::Heat,
+            ColorMap::Heat => ColorMap::Grayscale,
+            ColorMap::Grayscale => ColorMap::Spectrum,
+        }
+    }
+
+    // intensity goes from 0 (silent) to 1 (loudest)
+    fn color(self, intensity: f32) -> Color {
+        let intensity = intensity.clamp(0f32, 1f32);
+
+        match self {
+            ColorMap::Spectrum => {
+                // from dark blue up to bright red
+                let blue = Hsv::new(240f32, 1f32, intensity);
+                let color: Srgb = blue
+                    .shift_hue(RgbHue::from_degrees(-240f32 * intensity))
+                    .into_color();
+                Color::from_rgb(color.red, color.green, color.blue)
+            }
+            ColorMap::Heat => Color::from_rgb(
+                (3f32 * intensity).min(1f32),
+                (3f32 * intensity - 1f32).clamp(0f32, 1f32),
+                (3f32 * intensity - 2f32).clamp(0f32, 1f32),
+            ),
+            ColorMap::Grayscale => Color::from_rgb(intensity, intensity, intensity),
+        }
+    }
+}
+
+impl fmt::Display for ColorMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorMap::Spectrum => "spectrum",
+            ColorMap::Heat => "heat",
+            ColorMap::Grayscale => "grayscale",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for ColorMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spectrum" => Ok(ColorMap::Spectrum),
+            "heat" => Ok(ColorMap::Heat),
+            "grayscale" => Ok(ColorMap::Grayscale),
+            _ => Err(format!("unknown color map: {}", s)),
+        }
+    }
+}
 
 pub enum VisualizerMessage {
     SwitchDisplayContent,
-    ToggleNormalize,
-    ToggleSmooth,
-    ToggleFlashFlood,
-    ShiftMovingAvgRange(i32),
+    CycleDisplayType,
+    SetDisplayType(DisplayType),
+    CycleColorMap,
+    ToggleTrigger,
     ScaleUp,
     ScaleDown,
     ToggleOffCenter,
-    UpdateContent(Box<Clip>),
+    UpdateContent(Vec<AnalysisFrame>),
 }
 
 pub struct Visualizer {
@@ -38,29 +126,47 @@ pub struct Visualizer {
     content_type: crate::ContentType,
     display_type: crate::DisplayType,
 
-    content: Arc<Mutex<crate::Sides<Vec<f32>>>>,
+    content: crate::Sides<Vec<f32>>,
 
-    sound_transformer: SoundTransformer,
+    // what the latest frame was analyzed with, and where it ended in seconds
+    analysis: Option<(AnalysisSettings, f32)>,
+    // and the bands it was analyzed into, for the labels
+    bands: Arc<[Band]>,
 
     off_center: bool,
+
+    // spectra of past frames for the waterfall, oldest first
+    history: VecDeque<Vec<f32>>,
+    history_len: usize,
+    color_map: ColorMap,
+
+    // oscilloscope settings for raw content
+    trigger: bool,
+    trigger_level: f32,
+    scope_gain: f32,
+
+    // latest stereo samples for the goniometer, oldest first
+    gonio_trail: VecDeque<Sides<Vec<f32>>>,
 }
 
 impl Visualizer {
-    pub fn new(
-        width: u32,
-        height: u32,
-        content_type: crate::ContentType,
-        display_type: crate::DisplayType,
-        off_center: bool,
-    ) -> Self {
+    pub fn new(opt: &Opt) -> Self {
         Self {
-            width,
-            height,
-            content_type,
-            display_type,
-            content: Arc::new(Mutex::new(Sides::<Vec<f32>>::default())),
-            sound_transformer: SoundTransformer::default(),
-            off_center,
+            width: opt.width,
+            height: opt.height,
+            content_type: ContentType::Processed,
+            display_type: opt.display,
+            content: Sides::<Vec<f32>>::default(),
+            analysis: None,
+            bands: Arc::new([]),
+            off_center: true,
+            history: VecDeque::with_capacity(opt.history),
+            history_len: opt.history,
+            color_map: opt.color_map,
+            trigger: true,
+            trigger_level: opt.trigger_level,
+            scope_gain: 1f32,
+            gonio_trail: VecDeque::with_capacity(GONIO_PERSISTENCE),
         }
     }
 }
@@ -80,66 +186,105 @@ impl Visualizer {
                     }
                 };
             }
-            VisualizerMessage::ToggleNormalize => self.sound_transformer.toggle_norm(),
-            VisualizerMessage::ToggleSmooth => self.sound_transformer.toggle_smooth(),
-            VisualizerMessage::ToggleFlashFlood => self.sound_transformer.toggle_flash_flood(),
-            VisualizerMessage::ShiftMovingAvgRange(val) => {
-                self.sound_transformer.shift_moving_avg_range(val, true) // TODO: make debug actually be dynamic
+            VisualizerMessage::CycleDisplayType => {
+                self.display_type = self.display_type.next();
+                println!("showing {}", self.display_type);
             }
-            VisualizerMessage::ScaleUp => self.sound_transformer.shift_norm_scale(1.15f32),
-            VisualizerMessage::ScaleDown => self.sound_transformer.shift_norm_scale(1f32 / 1.15f32),
+            VisualizerMessage::SetDisplayType(display_type) => {
+                self.display_type = display_type;
+                println!("showing {}", self.display_type);
+            }
+            VisualizerMessage::CycleColorMap => {
+                self.color_map = self.color_map.next();
+                println!("using {} color map", self.color_map);
+            }
+            VisualizerMessage::ToggleTrigger => {
+                self.trigger = !self.trigger;
+                println!("trigger {}", if self.trigger { "on" } else { "off" });
+            }
+            VisualizerMessage::ScaleUp => self.scope_gain *= SCOPE_GAIN_STEP,
+            VisualizerMessage::ScaleDown => self.scope_gain /= SCOPE_GAIN_STEP,
             VisualizerMessage::ToggleOffCenter => self.off_center = !self.off_center,
-            VisualizerMessage::UpdateContent(clip) => {
-                let raw = Sides {
-                    left: clip.left.to_vec(),
-                    right: clip.right.to_vec(),
+            VisualizerMessage::UpdateContent(frames) => {
+                let latest_frame = match frames.last() {
+                    Some(frame) => frame,
+                    None => return,
                 };
+                self.analysis = Some((
+                    latest_frame.settings,
+                    latest_frame.time as f32 / latest_frame.sample_rate as f32,
+                ));
+                self.bands = latest_frame.bands.clone();
 
-                let to_freqs = |data, sample_rate| {
-                    samples_fft_to_spectrum(
-                        &windows::hamming_window(data),
-                        sample_rate,
-                        FrequencyLimit::All,
-                        None,
-                    )
-                    .expect("frequency spectrum conversion")
-                };
-
-                // define procedure ahead of time to apply to both left and right
-                let process = |new_raws, old_freqs: &Vec<f32>| {
-                    to_freqs(new_raws, clip.sample_rate)
-                        .data()
-                        .iter()
-                        //.map(|(_, v)| v.val()) // keep only the important part
-                        .zip(old_freqs.iter().chain(iter::repeat(&0f32))) // use old value too for smoothing, and lengthen the iterator if needed
-                        //.enumerate() // normalization uses this?
-                        .map(|((freq, new), old): (&(_, _), &f32)| {
-                            // apply the prettifying transformation
-                            self.sound_transformer.apply(*old, new.val(), freq.val())
-                        })
-                        .collect()
-                };
+                // only the newest frame of each batch has its samples
+                if let Some(raw) = frames.iter().rev().find_map(|frame| frame.raw.as_ref()) {
+                    if self.gonio_trail.len() >= GONIO_PERSISTENCE {
+                        self.gonio_trail.pop_front();
+                    }
+                    self.gonio_trail.push_back(Sides {
+                        left: latest(&raw.left, GONIO_SAMPLES).to_vec(),
+                        right: latest(&raw.right, GONIO_SAMPLES).to_vec(),
+                    });
 
-                let old_content_lock = self.content.clone();
-                let mut old_content = old_content_lock
-                    .lock()
-                    .expect("locked content in Visualizer::update");
+                    if let ContentType::Raw = self.content_type {
+                        self.content = raw.clone();
+                    }
+                }
+                if let ContentType::Raw = self.content_type {
+                    return;
+                }
 
-                let new_content = if let ContentType::Raw = self.content_type {
-                    raw
-                } else {
-                    Sides {
-                        left: process(&raw.left, &old_content.left),
-                        right: process(&raw.right, &old_content.right),
+                // every frame goes into the waterfall, so it follows the sound, not the UI
+                for frame in &frames {
+                    if self.history.len() >= self.history_len {
+                        self.history.pop_front();
                     }
-                };
+                    let scale = self.pixels_per_unit(frame);
+                    self.history.push_back(
+                        frame
+                            .rows
+                            .left
+                            .iter()
+                            .zip(frame.rows.right.iter())
+                            .map(|(left, right)| (left + right) / 2f32 * scale)
+                            .collect(),
+                    );
+                }
 
-                *old_content = new_content;
+                let scale = self.pixels_per_unit(latest_frame);
+                self.content = Sides {
+                    left: latest_frame
+                        .rows
+                        .left
+                        .iter()
+                        .map(|val| val * scale)
+                        .collect(),
+                    right: latest_frame
+                        .rows
+                        .right
+                        .iter()
+                        .map(|val| val * scale)
+                        .collect(),
+                };
             }
         };
     }
 
-    pub fn view(&self) -> Element<AppMessage> {
+    // the oscilloscope and the goniometer draw samples, the other displays draw the spectrum
+    pub fn shows_samples(&self) -> bool {
+        matches!(self.content_type, ContentType::Raw)
+            || matches!(self.display_type, DisplayType::Goniometer)
+    }
+
+    // pretty magnitudes already come in pixels, the others go from 0 to 1 across the lines view's half-width
+    fn pixels_per_unit(&self, frame: &AnalysisFrame) -> f32 {
+        match frame.settings.transformer.magnitude_scale() {
+            MagnitudeScale::Pretty => 1f32,
+            MagnitudeScale::Linear | MagnitudeScale::Decibel => self.width as f32 / 2f32,
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, AppMessage> {
         Container::new(
             Canvas::new(self)
                 .width(Length::Units(self.width as u16))
@@ -159,11 +304,36 @@ impl Program<AppMessage> for Visualizer {
         bounds: Rectangle,
         _cursor: Cursor,
     ) -> Vec<Geometry> {
-        // TODO: play around with colors more
-        // start at green, which is brighter than red, then rotate back to red, which doesn't actually yield back red :/
-        let red = Hsv::new(0f32, 1f32, 1f32);
-        //let red = yellow.shift_hue(LabHue::from_degrees(-120f32));
+        let mut frame = Frame::new(bounds.size());
+
+        match (self.display_type, self.content_type) {
+            // works on its own copy of the samples, regardless of content type
+            (DisplayType::Goniometer, _) => self.draw_goniometer(&mut frame),
+
+            // raw samples only make sense in the time domain, whatever the display type
+            (_, ContentType::Raw) => self.draw_oscilloscope(&mut frame, &self.content),
+
+            (DisplayType::Lines, _) => self.draw_lines(&mut frame, &self.content),
+            (DisplayType::Boxes, _) => self.draw_boxes(&mut frame, &self.content),
+            (DisplayType::Circle, _) => self.draw_circle(&mut frame, &self.content),
+            (DisplayType::Waterfall, _) => self.draw_waterfall(&mut frame),
+            (DisplayType::Bars, _) => self.draw_bars(&mut frame, &self.content),
+        }
 
+        // only spectra depend on the window
+        let shows_spectrum = !matches!(self.display_type, DisplayType::Goniometer)
+            && matches!(self.content_type, ContentType::Processed);
+        if shows_spectrum {
+            self.draw_status(&mut frame);
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+// renderers
+impl Visualizer {
+    fn draw_lines(&self, frame: &mut Frame, content: &Sides<Vec<f32>>) {
         let white = Color::from_rgb8(0xff, 0xff, 0xff);
         let stroke = Stroke {
             style: Style::Solid(white),
@@ -176,126 +346,662 @@ impl Program<AppMessage> for Visualizer {
             },
         };
 
-        let mut frame = Frame::new(bounds.size());
+        let center = frame.width() / 2f32;
 
-        let content_lock = self.content.clone();
-        let content = content_lock
-            .lock()
-            .expect("locked content in (Visualizer as Program<AppMessage>)::draw");
+        // bands spread over the full height, as thick as there's room for
+        let num_bands = content.left.len().min(content.right.len());
+        let band_height = frame.height() / num_bands.max(1) as f32;
+        let stroke = Stroke {
+            width: band_height.max(1f32),
+            ..stroke
+        };
 
-        match self.display_type {
-            crate::DisplayType::Lines => {
-                let center = frame.width() as f32 / 2f32;
+        let both_data = content.left.iter().zip(content.right.iter());
+        for (index, (left_val, right_val)) in both_data.enumerate() {
+            let position = (index as f32 + 0.5f32) / num_bands as f32;
+            let y = frame.height() * (1f32 - position);
+            let color = spectrum_color(position);
 
-                let both_data = content.left.iter().zip(content.right.iter());
-                for (index, (left_val, right_val)) in both_data.enumerate() {
-                    if index as u32 >= self.height {
-                        break;
-                    }
+            let center_point = Point { x: center, y };
+            let left_point = Point {
+                x: center - left_val,
+                y,
+            };
+            let right_point = Point {
+                x: center + right_val,
+                y,
+            };
 
-                    let y = (frame.height() as i32 - index as i32) as f32;
-                    let color_shift = RgbHue::from_degrees(360f32 * index as f32 / frame.height());
-                    let tip_color: Srgb = red.shift_hue(color_shift).into_color();
-                    let color = Color::from_rgb(tip_color.red, tip_color.green, tip_color.blue);
+            if self.off_center {
+                let mut path_builder = path::Builder::new();
+                path_builder.move_to(left_point);
+                path_builder.line_to(right_point);
+                let path = path_builder.build();
+                frame.stroke(
+                    &path,
+                    Stroke {
+                        style: Style::Gradient(Gradient::Linear(Linear {
+                            start: left_point,
+                            end: right_point,
+                            color_stops: vec![
+                                ColorStop {
+                                    offset: 0f32,
+                                    color,
+                                },
+                                ColorStop {
+                                    offset: 0.5f32,
+                                    color: white,
+                                },
+                                ColorStop {
+                                    offset: 1f32,
+                                    color,
+                                },
+                            ],
+                        })),
+                        ..stroke
+                    },
+                );
+            } else {
+                // do it in two parts, easier that way
 
-                    let center_point = Point { x: center, y };
-                    let left_point = Point {
-                        x: center - left_val,
-                        y,
-                    };
-                    let right_point = Point {
-                        x: center + right_val,
-                        y,
-                    };
+                let mut path_builder = path::Builder::new();
+                path_builder.move_to(left_point);
+                path_builder.line_to(center_point);
+                let path = path_builder.build();
+                frame.stroke(
+                    &path,
+                    Stroke {
+                        style: Style::Gradient(Gradient::Linear(Linear {
+                            start: left_point,
+                            end: center_point,
+                            color_stops: vec![
+                                ColorStop {
+                                    offset: 0f32,
+                                    color,
+                                },
+                                ColorStop {
+                                    offset: 1f32,
+                                    color: white,
+                                },
+                            ],
+                        })),
+                        ..stroke
+                    },
+                );
 
-                    if self.off_center {
-                        let mut path_builder = path::Builder::new();
-                        path_builder.move_to(left_point);
-                        path_builder.line_to(right_point);
-                        let path = path_builder.build();
-                        frame.stroke(
-                            &path,
-                            Stroke {
-                                style: Style::Gradient(Gradient::Linear(Linear {
-                                    start: left_point,
-                                    end: right_point,
-                                    color_stops: vec![
-                                        ColorStop {
-                                            offset: 0f32,
-                                            color,
-                                        },
-                                        ColorStop {
-                                            offset: 0.5f32,
-                                            color: white,
-                                        },
-                                        ColorStop {
-                                            offset: 1f32,
-                                            color,
-                                        },
-                                    ],
-                                })),
-                                ..stroke
-                            },
-                        );
+                let mut path_builder = path::Builder::new();
+                path_builder.move_to(center_point);
+                path_builder.line_to(right_point);
+                let path = path_builder.build();
+                frame.stroke(
+                    &path,
+                    Stroke {
+                        style: Style::Gradient(Gradient::Linear(Linear {
+                            start: center_point,
+                            end: right_point,
+                            color_stops: vec![
+                                ColorStop {
+                                    offset: 0f32,
+                                    color: white,
+                                },
+                                ColorStop {
+                                    offset: 1f32,
+                                    color,
+                                },
+                            ],
+                        })),
+                        ..stroke
+                    },
+                );
+            }
+        }
+    }
+
+    fn draw_boxes(&self, frame: &mut Frame, content: &Sides<Vec<f32>>) {
+        let white = Color::from_rgb8(0xff, 0xff, 0xff);
+
+        let center = frame.width() / 2f32;
+        let box_step = BOX_SIZE + BOX_GAP;
+        let max_boxes = (center / box_step) as usize;
+
+        // each row of boxes covers the bands the lines view draws at the same height
+        let num_bands = content.left.len().min(content.right.len());
+        let num_rows = (frame.height() / box_step) as usize;
+
+        for row in 0..num_rows {
+            let bands = slot_bands(row, num_rows, num_bands);
+            let left_val = band_peak(&content.left, bands.clone());
+            let right_val = band_peak(&content.right, bands);
+
+            let y = frame.height() - (row + 1) as f32 * box_step + BOX_GAP / 2f32;
+            let color = spectrum_color(row as f32 / num_rows as f32);
+
+            // left grows towards the left edge, right towards the right edge
+            for (direction, val) in [(-1f32, left_val), (1f32, right_val)] {
+                let lit = ((val / box_step).ceil().max(0f32) as usize).min(max_boxes);
+
+                for i in 0..lit {
+                    let offset = BOX_GAP / 2f32 + i as f32 * box_step;
+                    let x = if direction < 0f32 {
+                        center - offset - BOX_SIZE
                     } else {
-                        // do it in two parts, easier that way
-
-                        let mut path_builder = path::Builder::new();
-                        path_builder.move_to(left_point);
-                        path_builder.line_to(center_point);
-                        let path = path_builder.build();
-                        frame.stroke(
-                            &path,
-                            Stroke {
-                                style: Style::Gradient(Gradient::Linear(Linear {
-                                    start: left_point,
-                                    end: center_point,
-                                    color_stops: vec![
-                                        ColorStop {
-                                            offset: 0f32,
-                                            color,
-                                        },
-                                        ColorStop {
-                                            offset: 1f32,
-                                            color: white,
-                                        },
-                                    ],
-                                })),
-                                ..stroke
-                            },
-                        );
-
-                        let mut path_builder = path::Builder::new();
-                        path_builder.move_to(center_point);
-                        path_builder.line_to(right_point);
-                        let path = path_builder.build();
-                        frame.stroke(
-                            &path,
-                            Stroke {
-                                style: Style::Gradient(Gradient::Linear(Linear {
-                                    start: center_point,
-                                    end: right_point,
-                                    color_stops: vec![
-                                        ColorStop {
-                                            offset: 0f32,
-                                            color: white,
-                                        },
-                                        ColorStop {
-                                            offset: 1f32,
-                                            color,
-                                        },
-                                    ],
-                                })),
-                                ..stroke
-                            },
-                        );
-                    }
+                        center + offset
+                    };
+
+                    frame.fill_rectangle(
+                        Point { x, y },
+                        Size::new(BOX_SIZE, BOX_SIZE),
+                        mix(white, color, (i + 1) as f32 / lit as f32),
+                    );
+                }
+            }
+        }
+    }
+
+    fn draw_circle(&self, frame: &mut Frame, content: &Sides<Vec<f32>>) {
+        let white = Color::from_rgb8(0xff, 0xff, 0xff);
+        let stroke = Stroke {
+            style: Style::Solid(white),
+            width: 1f32,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Bevel,
+            line_dash: LineDash {
+                segments: &[],
+                offset: 0usize,
+            },
+        };
+
+        let center = frame.center();
+        let outer_radius = frame.width().min(frame.height()) / 2f32;
+        let inner_radius = outer_radius / 3f32;
+
+        // values are in pixels for the lines view's half-width, so shrink them to fit the ring
+        let scale = (outer_radius - inner_radius) / (frame.width() / 2f32);
+
+        // same bands as the lines view, spread over half a circle per channel
+        let num_bins = content.left.len().min(content.right.len());
+
+        frame.stroke(
+            &path::Path::circle(center, inner_radius),
+            Stroke {
+                style: Style::Solid(Color { a: 0.3f32, ..white }),
+                ..stroke
+            },
+        );
+
+        let both_data = content.left.iter().zip(content.right.iter());
+        for (index, (left_val, right_val)) in both_data.take(num_bins).enumerate() {
+            // low frequencies at the bottom, high at the top
+            let angle = PI * index as f32 / num_bins as f32;
+            let (sin, cos) = angle.sin_cos();
+            let color = spectrum_color(index as f32 / num_bins as f32);
+
+            // left channel on the left half, right channel on the right half
+            for (direction, val) in [(-1f32, left_val), (1f32, right_val)] {
+                let point_at = |radius: f32| Point {
+                    x: center.x + direction * sin * radius,
+                    y: center.y + cos * radius,
+                };
+
+                let inner_point = point_at(inner_radius);
+                let outer_point = point_at((inner_radius + val * scale).min(outer_radius));
+
+                frame.stroke(
+                    &path::Path::line(inner_point, outer_point),
+                    Stroke {
+                        style: Style::Gradient(Gradient::Linear(Linear {
+                            start: inner_point,
+                            end: outer_point,
+                            color_stops: vec![
+                                ColorStop {
+                                    offset: 0f32,
+                                    color: white,
+                                },
+                                ColorStop {
+                                    offset: 1f32,
+                                    color,
+                                },
+                            ],
+                        })),
+                        ..stroke
+                    },
+                );
+            }
+        }
+    }
+
+    fn draw_waterfall(&self, frame: &mut Frame) {
+        // same scale as the lines view, where a full half-width is the loudest
+        let max_val = frame.width() / 2f32;
+
+        // newest frame at the right edge, scrolling left
+        let column_width = frame.width() / self.history_len.max(1) as f32;
+        let first_column = self.history_len.saturating_sub(self.history.len());
+
+        let num_bands = self.history.back().map_or(0, |spectrum| spectrum.len());
+        let num_rows = (frame.height() / WATERFALL_CELL) as usize;
+
+        for row in 0..num_rows {
+            let bands = slot_bands(row, num_rows, num_bands);
+            let y = frame.height() - (row + 1) as f32 * WATERFALL_CELL;
+
+            let levels = self.history.iter().map(|spectrum| {
+                let intensity = (band_peak(spectrum, bands.clone()) / max_val).clamp(0f32, 1f32);
+                (intensity * (COLOR_LEVELS - 1) as f32).round() as u8
+            });
+
+            // merge neighboring cells of the same color into one rectangle, silence is left as background
+            let mut run_start = 0usize;
+            let mut run_level = 0u8;
+            for (column, level) in levels.chain(iter::once(0u8)).enumerate() {
+                if level == run_level {
+                    continue;
+                }
+
+                if run_level > 0 {
+                    frame.fill_rectangle(
+                        Point {
+                            x: (first_column + run_start) as f32 * column_width,
+                            y,
+                        },
+                        Size::new((column - run_start) as f32 * column_width, WATERFALL_CELL),
+                        self.color_map
+                            .color(run_level as f32 / (COLOR_LEVELS - 1) as f32),
+                    );
                 }
 
-                vec![frame.into_geometry()]
+                run_start = column;
+                run_level = level;
+            }
+        }
+    }
+
+    // a real-time analyzer, left and right bars side by side for every band, named underneath
+    fn draw_bars(&self, frame: &mut Frame, content: &Sides<Vec<f32>>) {
+        let white = Color::from_rgb8(0xff, 0xff, 0xff);
+
+        let settings = match self.analysis {
+            Some((settings, _)) => settings,
+            None => return,
+        };
+        let bands = &self.bands;
+        let num_bands = bands.len().min(content.left.len()).min(content.right.len());
+        if num_bands == 0 {
+            return;
+        }
+
+        let max_height = frame.height() - BAR_LABEL_HEIGHT;
+        let slot_width = frame.width() / num_bands as f32;
+        let bar_width = ((slot_width - BAR_GAP) / 2f32).max(1f32);
+
+        // values are in pixels for the lines view's half-width, so stretch them to the full height
+        let scale = max_height / (frame.width() / 2f32);
+
+        // as many labels as fit without overlapping
+        let label_every = ((BAR_LABEL_WIDTH / slot_width).ceil() as usize).max(1);
+
+        for (index, band) in bands.iter().take(num_bands).enumerate() {
+            let x = index as f32 * slot_width + BAR_GAP / 2f32;
+            let color = spectrum_color(index as f32 / num_bands as f32);
+
+            for (side, (val, color)) in [
+                (content.left[index], color),
+                (content.right[index], mix(white, color, 0.6f32)),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let height = (val * scale).clamp(0f32, max_height);
+                frame.fill_rectangle(
+                    Point {
+                        x: x + side as f32 * bar_width,
+                        y: max_height - height,
+                    },
+                    Size::new(bar_width, height),
+                    color,
+                );
+            }
+
+            if index % label_every == 0 {
+                frame.fill_text(Text {
+                    content: settings.band_scale.label(band),
+                    position: Point {
+                        x: index as f32 * slot_width + slot_width / 2f32,
+                        y: max_height + 4f32,
+                    },
+                    color: white,
+                    size: 12f32,
+                    horizontal_alignment: Horizontal::Center,
+                    ..Text::default()
+                });
             }
-            crate::DisplayType::Boxes => todo!(),
-            crate::DisplayType::Circle => todo!(),
         }
     }
+
+    fn draw_oscilloscope(&self, frame: &mut Frame, content: &Sides<Vec<f32>>) {
+        let white = Color::from_rgb8(0xff, 0xff, 0xff);
+        let stroke = Stroke {
+            style: Style::Solid(white),
+            width: 1f32,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            line_dash: LineDash {
+                segments: &[],
+                offset: 0usize,
+            },
+        };
+
+        let len = content.left.len().min(content.right.len());
+        if len < 2 {
+            return;
+        }
+
+        // show half the buffer so the trigger point can be anywhere in the first half
+        let window = len / 2;
+        let start = if self.trigger {
+            find_trigger(content, window, self.trigger_level).unwrap_or(len - window)
+        } else {
+            len - window
+        };
+
+        // left channel stacked above the right one
+        let lane_height = frame.height() / 2f32;
+        let x_step = frame.width() / (window - 1) as f32;
+
+        for (lane, (samples, color)) in [
+            (&content.left, spectrum_color(0f32)),
+            (&content.right, spectrum_color(0.5f32)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let zero_y = lane_height * (lane as f32 + 0.5f32);
+            let to_y = |sample: f32| {
+                let offset = (sample * self.scope_gain).clamp(-1f32, 1f32) * lane_height / 2f32;
+                zero_y - offset
+            };
+
+            frame.stroke(
+                &path::Path::line(
+                    Point { x: 0f32, y: zero_y },
+                    Point {
+                        x: frame.width(),
+                        y: zero_y,
+                    },
+                ),
+                Stroke {
+                    style: Style::Solid(Color { a: 0.3f32, ..white }),
+                    ..stroke
+                },
+            );
+
+            let mut path_builder = path::Builder::new();
+            for (i, sample) in samples[start..start + window].iter().enumerate() {
+                let point = Point {
+                    x: i as f32 * x_step,
+                    y: to_y(*sample),
+                };
+
+                if i == 0 {
+                    path_builder.move_to(point);
+                } else {
+                    path_builder.line_to(point);
+                }
+            }
+            frame.stroke(
+                &path_builder.build(),
+                Stroke {
+                    style: Style::Solid(color),
+                    ..stroke
+                },
+            );
+        }
+    }
+
+    fn draw_goniometer(&self, frame: &mut Frame) {
+        let white = Color::from_rgb8(0xff, 0xff, 0xff);
+        let stroke = Stroke {
+            style: Style::Solid(white),
+            width: 1f32,
+            line_cap: LineCap::Round,
+            line_join: LineJoin::Round,
+            line_dash: LineDash {
+                segments: &[],
+                offset: 0usize,
+            },
+        };
+        let guide = Stroke {
+            style: Style::Solid(Color { a: 0.3f32, ..white }),
+            ..stroke
+        };
+
+        // leave room for the correlation meter at the bottom
+        let scope_height = frame.height() - CORRELATION_METER_HEIGHT;
+        let center = Point {
+            x: frame.width() / 2f32,
+            y: scope_height / 2f32,
+        };
+        let radius = frame.width().min(scope_height) / 2f32;
+
+        // mid is vertical and side is horizontal, so L and R end up on the diagonals
+        let to_point = |left: f32, right: f32| {
+            let mid = (left + right) * FRAC_1_SQRT_2 * self.scope_gain;
+            let side = (right - left) * FRAC_1_SQRT_2 * self.scope_gain;
+            Point {
+                x: center.x + side.clamp(-1f32, 1f32) * radius,
+                y: center.y - mid.clamp(-1f32, 1f32) * radius,
+            }
+        };
+
+        // guides along the M, S, L and R axes
+        let diagonal = radius * FRAC_1_SQRT_2;
+        for (dx, dy) in [
+            (0f32, radius),
+            (radius, 0f32),
+            (-diagonal, diagonal),
+            (diagonal, diagonal),
+        ] {
+            frame.stroke(
+                &path::Path::line(
+                    Point {
+                        x: center.x - dx,
+                        y: center.y + dy,
+                    },
+                    Point {
+                        x: center.x + dx,
+                        y: center.y - dy,
+                    },
+                ),
+                guide.clone(),
+            );
+        }
+
+        // older frames fade out
+        let trail_len = self.gonio_trail.len();
+        for (age, samples) in self.gonio_trail.iter().rev().enumerate() {
+            let alpha = 1f32 - age as f32 / trail_len as f32;
+
+            let mut path_builder = path::Builder::new();
+            for (i, (left, right)) in samples.left.iter().zip(samples.right.iter()).enumerate() {
+                if i == 0 {
+                    path_builder.move_to(to_point(*left, *right));
+                } else {
+                    path_builder.line_to(to_point(*left, *right));
+                }
+            }
+            frame.stroke(
+                &path_builder.build(),
+                Stroke {
+                    style: Style::Solid(Color {
+                        a: alpha,
+                        ..spectrum_color(1f32 / 3f32)
+                    }),
+                    ..stroke
+                },
+            );
+        }
+
+        // correlation meter, from -1 (out of phase) to +1 (mono)
+        let correlation = self
+            .gonio_trail
+            .back()
+            .map(|samples| correlation(&samples.left, &samples.right))
+            .unwrap_or(0f32);
+
+        let meter_y = scope_height + CORRELATION_METER_HEIGHT / 2f32;
+        let meter_left = frame.width() / 8f32;
+        let meter_width = frame.width() - 2f32 * meter_left;
+        let meter_x = |val: f32| meter_left + (val + 1f32) / 2f32 * meter_width;
+
+        frame.stroke(
+            &path::Path::line(
+                Point {
+                    x: meter_x(-1f32),
+                    y: meter_y,
+                },
+                Point {
+                    x: meter_x(1f32),
+                    y: meter_y,
+                },
+            ),
+            guide,
+        );
+        frame.fill_rectangle(
+            Point {
+                x: meter_x(0f32.min(correlation)),
+                y: meter_y - 4f32,
+            },
+            Size::new(correlation.abs() * meter_width / 2f32, 8f32),
+            if correlation < 0f32 {
+                spectrum_color(0f32)
+            } else {
+                spectrum_color(1f32 / 3f32)
+            },
+        );
+
+        for (val, label) in [(-1f32, "-1"), (0f32, "0"), (1f32, "+1")] {
+            frame.fill_text(Text {
+                content: label.to_string(),
+                position: Point {
+                    x: meter_x(val),
+                    y: meter_y + 6f32,
+                },
+                color: white,
+                size: 12f32,
+                horizontal_alignment: Horizontal::Center,
+                ..Text::default()
+            });
+        }
+    }
+
+    // what the spectrum is being computed with, in the top left corner
+    fn draw_status(&self, frame: &mut Frame) {
+        let (settings, seconds) = match self.analysis {
+            Some(analysis) => analysis,
+            None => return,
+        };
+
+        let bands = match settings.band_scale {
+            BandScale::Log => format!("{} log", settings.num_bands),
+            BandScale::Mel => format!("{} mel", settings.num_bands),
+            band_scale => band_scale.to_string(),
+        };
+
+        let gain = match settings.transformer.gain_db() {
+            gain if gain.abs() < 0.05f32 => String::new(),
+            gain => format!(" {:+.1} dB gain", gain),
+        };
+        let magnitudes = match settings.transformer.magnitude_scale() {
+            MagnitudeScale::Pretty => "pretty".to_string(),
+            MagnitudeScale::Linear => format!("linear{}", gain),
+            MagnitudeScale::Decibel => {
+                let (min_db, max_db) = settings.transformer.db_range();
+                format!("{} to {} dBFS{}", min_db, max_db, gain)
+            }
+        };
+
+        let weighting = match settings.transformer.active_weighting() {
+            Some(Weighting::Pink) => format!(
+                "pink {:+} dB/oct weighting",
+                settings.transformer.pink_slope()
+            ),
+            Some(weighting) => format!("{} weighting", weighting),
+            None => "unweighted".to_string(),
+        };
+
+        frame.fill_text(Text {
+            content: format!(
+                "{} window, {} samples, hop {}, {} bands, {}, {}, {:.2} s",
+                settings.window,
+                settings.clip_size,
+                settings.hop_size,
+                bands,
+                magnitudes,
+                weighting,
+                seconds
+            ),
+            position: Point { x: 4f32, y: 4f32 },
+            color: Color::from_rgb8(0xff, 0xff, 0xff),
+            size: 12f32,
+            ..Text::default()
+        });
+    }
+}
+
+// helpers -----------------------------------------------------------------------------------------
+
+// start at red and rotate through the hues as position goes from 0 to 1
+fn spectrum_color(position: f32) -> Color {
+    // TODO: play around with colors more
+    let red = Hsv::new(0f32, 1f32, 1f32);
+
+    let tip_color: Srgb = red
+        .shift_hue(RgbHue::from_degrees(360f32 * position))
+        .into_color();
+    Color::from_rgb(tip_color.red, tip_color.green, tip_color.blue)
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    Color::from_rgb(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+    )
+}
+
+// first rising crossing of the level in the mid signal, leaving room for a full window after it
+fn find_trigger(content: &Sides<Vec<f32>>, window: usize, level: f32) -> Option<usize> {
+    let mid = |i: usize| (content.left[i] + content.right[i]) / 2f32;
+    let last = content.left.len().min(content.right.len()) - window;
+
+    (1..=last).find(|&i| mid(i - 1) < level && mid(i) >= level)
+}
+
+// the most recent samples of a buffer, or all of them if there aren't enough
+fn latest(data: &[f32], count: usize) -> &[f32] {
+    &data[data.len().saturating_sub(count)..]
+}
+
+// normalized cross-correlation at zero lag, 0 for silence
+fn correlation(left: &[f32], right: &[f32]) -> f32 {
+    let (cross, left_energy, right_energy) = left.iter().zip(right.iter()).fold(
+        (0f32, 0f32, 0f32),
+        |(cross, left_energy, right_energy), (l, r)| {
+            (cross + l * r, left_energy + l * l, right_energy + r * r)
+        },
+    );
+
+    let energy = (left_energy * right_energy).sqrt();
+    if energy > 0f32 {
+        cross / energy
+    } else {
+        0f32
+    }
+}
+
+fn band_peak(data: &[f32], bands: Range<usize>) -> f32 {
+    data.iter()
+        .skip(bands.start)
+        .take(bands.len())
+        .fold(0f32, |peak, val| peak.max(*val))
 }